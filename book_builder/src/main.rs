@@ -0,0 +1,93 @@
+use chess_engine::pgn;
+use chess_engine::polyglot::BookBuilder;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::process;
+
+const USAGE: &str = "usage: book_builder [options] -o <book.bin> <games.pgn>...
+
+options:
+    -o, --output <file>    book to write
+    --max-ply <n>          only use the first n plies of every game (default 1024)
+    --min-games <n>        drop moves seen in fewer than n games (default 3)
+    --player <name>        only use moves played by this player
+    --win <n>              score for a won game (default 2)
+    --draw <n>             score for a drawn game (default 1)
+    --loss <n>             score for a lost game (default 0)";
+
+fn main() {
+    let mut builder = BookBuilder::new();
+    let mut output = None;
+    let mut inputs = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(value(&arg, args.next())),
+            "--max-ply" => builder.max_ply = number(&arg, args.next()),
+            "--min-games" => builder.min_games = number(&arg, args.next()),
+            "--player" => builder.player = Some(value(&arg, args.next())),
+            "--win" => builder.win_score = number(&arg, args.next()),
+            "--draw" => builder.draw_score = number(&arg, args.next()),
+            "--loss" => builder.loss_score = number(&arg, args.next()),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with('-') => fail(&format!("unknown option {}", arg)),
+            _ => inputs.push(arg),
+        }
+    }
+
+    let output = output.unwrap_or_else(|| fail("no output book given"));
+    if inputs.is_empty() {
+        fail("no PGN files given");
+    }
+
+    let mut games = 0;
+    let mut plies = 0;
+    for input in &inputs {
+        let text = fs::read_to_string(input)
+            .unwrap_or_else(|e| fail(&format!("couldn't read {}: {}", input, e)));
+
+        for game in pgn::parse(&text) {
+            let used = builder.add_game(&game);
+            if used > 0 {
+                games += 1;
+                plies += used;
+            }
+        }
+    }
+
+    let file = File::create(&output)
+        .unwrap_or_else(|e| fail(&format!("couldn't create {}: {}", output, e)));
+    let mut writer = BufWriter::new(file);
+    let entries = builder
+        .write(&mut writer)
+        .and_then(|entries| writer.flush().map(|_| entries))
+        .unwrap_or_else(|e| fail(&format!("couldn't write {}: {}", output, e)));
+
+    println!(
+        "used {} plies from {} games, wrote {} entries to {}",
+        plies, games, entries, output
+    );
+}
+
+fn value(option: &str, value: Option<String>) -> String {
+    value.unwrap_or_else(|| fail(&format!("{} needs a value", option)))
+}
+
+fn number<T: std::str::FromStr>(option: &str, value: Option<String>) -> T {
+    let value = self::value(option, value);
+
+    value
+        .parse()
+        .unwrap_or_else(|_| fail(&format!("{} expects a number, got {}", option, value)))
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(1);
+}
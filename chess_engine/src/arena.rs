@@ -0,0 +1,388 @@
+use crate::fen;
+use crate::game::{Game, Outcome};
+use crate::pgn::{self, PgnMove};
+use crate::piece::*;
+use crate::polyglot;
+use crate::review;
+use crate::uci::{self, Engine, Limit, UciMove};
+
+use std::collections::HashMap;
+use std::io;
+use std::time::{Duration, Instant};
+
+// How long past its time an engine may take for a move before it loses, and
+// how long to wait for a depth limited search
+const TIME_MARGIN: Duration = Duration::from_millis(100);
+const GRACE: Duration = Duration::from_secs(10);
+const DEPTH_TIMEOUT: Duration = Duration::from_secs(3600);
+
+// Where a game starts: a position and the moves played from it before the
+// engines take over
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Opening {
+    pub fen: String,
+    pub moves: Vec<UciMove>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TimeControl {
+    // Milliseconds for the game and added after every move
+    Clock { base: u64, increment: u64 },
+    // Milliseconds per move
+    MoveTime(u64),
+    Depth(u32),
+}
+
+// Scores are in centipawns and the move counts in full moves. Both engines
+// have to agree for `resign_moves` moves in a row that one side is losing by
+// `resign_score`, or that the game is within `draw_score` of equal after
+// move `draw_after`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Adjudication {
+    pub resign_score: Option<i32>,
+    pub resign_moves: usize,
+    pub draw_score: Option<i32>,
+    pub draw_moves: usize,
+    pub draw_after: usize,
+    // Games still going after this many moves are drawn
+    pub max_moves: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameRecord {
+    pub white: String,
+    pub black: String,
+    pub opening: Opening,
+    // All moves from the opening position, the opening's own moves first
+    pub moves: Vec<UciMove>,
+    pub sans: Vec<String>,
+    // None when the game couldn't be finished, for example when an engine
+    // played a move this crate doesn't consider legal
+    pub result: Option<Outcome>,
+    pub termination: String,
+}
+
+impl Opening {
+    pub fn start() -> Opening {
+        Opening {
+            fen: fen::START.to_owned(),
+            moves: Vec::new(),
+        }
+    }
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Adjudication {
+            resign_score: Some(1000),
+            resign_moves: 3,
+            draw_score: Some(10),
+            draw_moves: 8,
+            draw_after: 40,
+            max_moves: None,
+        }
+    }
+}
+
+// Plays one game between two engines from `opening`. Errors are only given
+// when talking to an engine fails
+pub fn play_game(
+    white: &mut Engine,
+    black: &mut Engine,
+    opening: &Opening,
+    time_control: TimeControl,
+    adjudication: &Adjudication,
+) -> io::Result<GameRecord> {
+    let mut game = fen::parse(&opening.fen).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("bad opening position {}", opening.fen),
+        )
+    })?;
+    let fields: Vec<&str> = opening.fen.split_whitespace().collect();
+    let mut halfmoves: usize = fields.get(4).and_then(|f| f.parse().ok()).unwrap_or(0);
+
+    let mut record = GameRecord {
+        white: white.name.clone(),
+        black: black.name.clone(),
+        opening: opening.clone(),
+        moves: Vec::new(),
+        sans: Vec::new(),
+        result: None,
+        termination: String::new(),
+    };
+    let mut positions = HashMap::new();
+    *positions.entry(polyglot::key(&game)).or_insert(0) += 1;
+
+    for mv in &opening.moves {
+        if !record.play(&mut game, mv, &mut halfmoves) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("illegal opening move {}", mv),
+            ));
+        }
+        *positions.entry(polyglot::key(&game)).or_insert(0) += 1;
+    }
+
+    white.new_game()?;
+    black.new_game()?;
+
+    // Time left on the clocks and the scores the engines gave, both from
+    // white's side
+    let mut clocks = match time_control {
+        TimeControl::Clock { base, .. } => [base as i64; 2],
+        _ => [0; 2],
+    };
+    let mut scores: Vec<Option<i32>> = Vec::new();
+
+    let (result, termination) = loop {
+        let color = game.curr_player;
+        let engine = match color {
+            Color::White => &mut *white,
+            Color::Black => &mut *black,
+        };
+
+        if !has_legal_move(&mut game) {
+            break if game.board.is_king_attacked(color) {
+                (
+                    Some(Outcome::win_for(color.other())),
+                    "checkmate".to_owned(),
+                )
+            } else {
+                (Some(Outcome::Draw), "stalemate".to_owned())
+            };
+        }
+        if halfmoves >= 100 {
+            break (Some(Outcome::Draw), "fifty move rule".to_owned());
+        }
+        if positions.values().any(|count| *count >= 3) {
+            break (Some(Outcome::Draw), "threefold repetition".to_owned());
+        }
+        // Neither side can mate
+        if game.insufficient_material(Color::White) && game.insufficient_material(Color::Black) {
+            break (Some(Outcome::Draw), "insufficient material".to_owned());
+        }
+        if adjudication
+            .max_moves
+            .is_some_and(|max| record.moves.len() - opening.moves.len() >= 2 * max)
+        {
+            break (Some(Outcome::Draw), "adjudication: move limit".to_owned());
+        }
+        if let Some(adjudicated) = adjudicate(&scores, record.moves.len(), adjudication) {
+            break adjudicated;
+        }
+
+        let (limit, timeout) = match time_control {
+            TimeControl::Clock { increment, .. } => {
+                let left = clocks[color as usize].max(0) as u64;
+                let limit = Limit::Clock {
+                    wtime: clocks[Color::White as usize].max(0) as u64,
+                    btime: clocks[Color::Black as usize].max(0) as u64,
+                    winc: increment,
+                    binc: increment,
+                };
+                (limit, Duration::from_millis(left) + TIME_MARGIN)
+            }
+            TimeControl::MoveTime(time) => {
+                (Limit::MoveTime(time), Duration::from_millis(time) + GRACE)
+            }
+            TimeControl::Depth(depth) => (Limit::Depth(depth), DEPTH_TIMEOUT),
+        };
+
+        let start = Instant::now();
+        engine.position_fen(&opening.fen, &record.moves)?;
+        engine.go(limit)?;
+        let (best, info) = match engine.finish_search(timeout) {
+            Ok(found) => found,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                engine.stop()?;
+                break (
+                    Some(Outcome::win_for(color.other())),
+                    "time forfeit".to_owned(),
+                );
+            }
+            Err(e) => return Err(e),
+        };
+
+        if let TimeControl::Clock { increment, .. } = time_control {
+            let clock = &mut clocks[color as usize];
+            *clock -= start.elapsed().as_millis() as i64;
+            if *clock < -(TIME_MARGIN.as_millis() as i64) {
+                break (
+                    Some(Outcome::win_for(color.other())),
+                    "time forfeit".to_owned(),
+                );
+            }
+            *clock += increment as i64;
+        }
+
+        let best = match best {
+            Some(best) => best,
+            None => break (None, format!("{} gave no move", color_name(color))),
+        };
+        if !record.play(&mut game, &best, &mut halfmoves) {
+            break (
+                None,
+                format!("{} played unknown move {}", color_name(color), best),
+            );
+        }
+        *positions.entry(polyglot::key(&game)).or_insert(0) += 1;
+
+        let score = info.and_then(|info| info.score).map(review::centipawns);
+        scores.push(score.map(|score| match color {
+            Color::White => score,
+            Color::Black => -score,
+        }));
+    };
+
+    record.result = result;
+    record.termination = termination;
+    Ok(record)
+}
+
+// Decides the game from the engines' scores
+fn adjudicate(
+    scores: &[Option<i32>],
+    plies: usize,
+    adjudication: &Adjudication,
+) -> Option<(Option<Outcome>, String)> {
+    let last = |moves: usize| -> Option<Vec<i32>> {
+        if moves == 0 || scores.len() < 2 * moves {
+            return None;
+        }
+        scores[scores.len() - 2 * moves..].iter().copied().collect()
+    };
+
+    if let (Some(threshold), Some(recent)) =
+        (adjudication.resign_score, last(adjudication.resign_moves))
+    {
+        if recent.iter().all(|score| *score >= threshold) {
+            return Some((
+                Some(Outcome::WhiteWins),
+                "adjudication: black resigns".to_owned(),
+            ));
+        }
+        if recent.iter().all(|score| *score <= -threshold) {
+            return Some((
+                Some(Outcome::BlackWins),
+                "adjudication: white resigns".to_owned(),
+            ));
+        }
+    }
+
+    if let (Some(threshold), Some(recent)) =
+        (adjudication.draw_score, last(adjudication.draw_moves))
+    {
+        if plies >= 2 * adjudication.draw_after
+            && recent.iter().all(|score| score.abs() <= threshold)
+        {
+            return Some((Some(Outcome::Draw), "adjudication: draw".to_owned()));
+        }
+    }
+
+    None
+}
+
+impl GameRecord {
+    // Plays `mv` and records it, false if it isn't legal
+    fn play(&mut self, game: &mut Game, mv: &UciMove, halfmoves: &mut usize) -> bool {
+        let san = match pgn::to_san(
+            game,
+            &PgnMove {
+                from: mv.from,
+                to: mv.to,
+                promotion: mv.promotion,
+            },
+        ) {
+            Some(san) => san,
+            None => return false,
+        };
+
+        let pawn = game.board.board_squares[mv.from.0][mv.from.1]
+            .piece
+            .is_some_and(|piece| piece.piece_type == PieceType::Pawn);
+        let capture = game.board.board_squares[mv.to.0][mv.to.1].piece.is_some();
+
+        if !uci::play_move(game, mv) {
+            return false;
+        }
+
+        *halfmoves = if pawn || capture { 0 } else { *halfmoves + 1 };
+        self.moves.push(*mv);
+        self.sans.push(san);
+        true
+    }
+
+    pub fn result_text(&self) -> String {
+        match self.result {
+            Some(outcome) => outcome.to_string(),
+            None => "*".to_owned(),
+        }
+    }
+
+    // The game as PGN, the position is given in a FEN tag unless the game
+    // starts from the start position
+    pub fn to_pgn(&self, event: &str, round: usize) -> String {
+        let result = self.result_text();
+        let mut tags: Vec<(String, String)> = vec![
+            ("Event".to_owned(), event.to_owned()),
+            ("Site".to_owned(), "?".to_owned()),
+            ("Date".to_owned(), "????.??.??".to_owned()),
+            ("Round".to_owned(), round.to_string()),
+            ("White".to_owned(), self.white.clone()),
+            ("Black".to_owned(), self.black.clone()),
+            ("Result".to_owned(), result.clone()),
+        ];
+
+        let fields: Vec<&str> = self.opening.fen.split_whitespace().collect();
+        if fields[..4] != fen::START.split_whitespace().collect::<Vec<_>>()[..4] {
+            tags.push(("SetUp".to_owned(), "1".to_owned()));
+            tags.push(("FEN".to_owned(), self.opening.fen.clone()));
+        }
+        tags.push(("Termination".to_owned(), self.termination.clone()));
+
+        let mut number: usize = fields.get(5).and_then(|f| f.parse().ok()).unwrap_or(1);
+        let mut white = fields.get(1) != Some(&"b");
+
+        let mut tokens = Vec::new();
+        if !white {
+            tokens.push(format!("{}...", number));
+        }
+        for san in &self.sans {
+            if white {
+                tokens.push(format!("{}.", number));
+            } else {
+                number += 1;
+            }
+            tokens.push(san.clone());
+            white = !white;
+        }
+        tokens.push(format!("{{{}}}", self.termination));
+        tokens.push(result);
+
+        pgn::write(&tags, &tokens)
+    }
+}
+
+fn has_legal_move(game: &mut Game) -> bool {
+    for x in 0..8 {
+        for y in 0..8 {
+            let own = game.board.board_squares[x][y]
+                .piece
+                .is_some_and(|piece| piece.color == game.curr_player);
+
+            if own && !game.get_available_moves((x, y)).is_empty() {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+fn color_name(color: Color) -> &'static str {
+    match color {
+        Color::White => "white",
+        Color::Black => "black",
+    }
+}
@@ -193,18 +193,16 @@ impl Board {
         piece: piece::Piece,
     ) -> HashSet<Vec<usize>> {
         let mut available_moves = piece.clone().available_moves((i, j), self);
+        let from = (i, j);
         if piece.piece_type == piece::PieceType::King {
-            // Remove moves where the king is attacked
+            // Remove moves where the king is attacked, with the king off its
+            // square so it can't hide behind itself
             for i in available_moves.clone() {
-                if self.is_square_attacked((i[0], i[1]), piece.color) {
-                    available_moves.remove(&[i[0], i[1]].to_vec());
-                }
                 let mut fake_board = self.clone();
-                if fake_board.blocked_by_enemy((i[0], i[1]), piece.color) {
-                    fake_board.board_squares[i[0]][i[1]].piece = None;
-                    if fake_board.is_square_attacked((i[0], i[1]), piece.color) {
-                        available_moves.remove(&[i[0], i[1]].to_vec());
-                    }
+                fake_board.board_squares[from.0][from.1].piece = None;
+                fake_board.board_squares[i[0]][i[1]].piece = Some(piece);
+                if fake_board.is_square_attacked((i[0], i[1]), piece.color) {
+                    available_moves.remove(&[i[0], i[1]].to_vec());
                 }
             }
 
@@ -212,36 +210,10 @@ impl Board {
             let (short, long) = self.castling(piece.color);
 
             if long {
-                if piece.color == piece::Color::White {
-                    available_moves.insert(vec![2, 0]);
-                } else {
-                    available_moves.insert(vec![2, 7]);
-                }
-            } else if short {
-                if piece.color == piece::Color::White {
-                    available_moves.insert(vec![6, 0]);
-                } else {
-                    available_moves.insert(vec![6, 7]);
-                }
+                available_moves.insert(vec![2, from.1]);
             }
-        }
-
-        // Insert castling moves for the rook if it is possible
-        if piece.piece_type == piece::PieceType::Rook {
-            let (short, long) = self.castling(piece.color);
-
-            if long {
-                if piece.color == piece::Color::White {
-                    available_moves.insert(vec![3, 0]);
-                } else {
-                    available_moves.insert(vec![3, 7]);
-                }
-            } else if short {
-                if piece.color == piece::Color::White {
-                    available_moves.insert(vec![5, 0]);
-                } else {
-                    available_moves.insert(vec![5, 7]);
-                }
+            if short {
+                available_moves.insert(vec![6, from.1]);
             }
         }
 
@@ -249,10 +221,9 @@ impl Board {
         if piece.piece_type != piece::PieceType::King {
             for i in available_moves.clone() {
                 let mut fake_board = self.clone();
-                let (x, y) = self.find_piece(piece.piece_type, piece.color);
                 fake_board.board_squares[i[0]][i[1]].piece =
                     Some(piece::Piece::new(piece.piece_type, piece.color));
-                fake_board.board_squares[x][y].piece = None;
+                fake_board.board_squares[from.0][from.1].piece = None;
                 if fake_board.is_king_attacked(piece.color) {
                     available_moves.remove(&[i[0], i[1]].to_vec());
                 }
@@ -262,54 +233,55 @@ impl Board {
         available_moves
     }
 
-    // Check if castling is possible
+    // Which ways `player_color` can castle, (short, long). Neither the king
+    // nor the rook may have left (or been captured on) its square, the
+    // squares between them must be empty and the king can't castle out of,
+    // through or into check
     pub fn castling(&mut self, player_color: piece::Color) -> (bool, bool) {
-        if (self.history.contains(&[4, 0].to_vec()) && player_color == piece::Color::White)
-            || (self.history.contains(&[4, 7].to_vec()) && player_color == piece::Color::Black)
-            || self.is_king_attacked(player_color)
-        {
-            (false, false)
-        } else {
-            let color_offset = match player_color {
-                piece::Color::White => 0,
-                piece::Color::Black => 7,
-            };
-            let mut count = 0;
+        let y = match player_color {
+            piece::Color::White => 0,
+            piece::Color::Black => 7,
+        };
 
-            for i in 1..4 {
-                match self.board_squares[i][color_offset].piece {
-                    None => count += 1,
-                    _ => {}
-                }
-                if !self.is_square_attacked((i, color_offset), player_color) {
-                    count += 1;
-                }
-            }
-            let mut count_2 = 0;
-            for i in 5..7 {
-                match self.board_squares[i][color_offset].piece {
-                    None => count_2 += 1,
-                    _ => {}
-                }
-                if !self.is_square_attacked((i, color_offset), player_color) {
-                    count_2 += 1;
-                }
-            }
-            let mut long = false;
-            if count == 6 {
-                long = true
-            }
-            let mut short = false;
-            if count_2 == 4 {
-                short = true;
-            }
-            (short, long)
+        if !self.at_home((4, y), piece::PieceType::King, player_color)
+            || self.is_square_attacked((4, y), player_color)
+        {
+            return (false, false);
         }
+
+        let short = self.can_castle(player_color, (7, y), &[5, 6], &[5, 6]);
+        let long = self.can_castle(player_color, (0, y), &[1, 2, 3], &[2, 3]);
+        (short, long)
+    }
+
+    // Whether a piece of `kind` has stood on `loc` all game
+    fn at_home(&self, loc: (usize, usize), kind: piece::PieceType, color: piece::Color) -> bool {
+        !self.history.contains(&vec![loc.0, loc.1])
+            && self.board_squares[loc.0][loc.1]
+                .piece
+                .is_some_and(|p| p.piece_type == kind && p.color == color)
     }
 
-    // Insert castling moves if castling is possible
+    // Castling with the rook on `rook`, the files in `empty` must be empty and
+    // the king crosses the ones in `safe`
+    fn can_castle(
+        &mut self,
+        color: piece::Color,
+        rook: (usize, usize),
+        empty: &[usize],
+        safe: &[usize],
+    ) -> bool {
+        let y = rook.1;
+
+        self.at_home(rook, piece::PieceType::Rook, color)
+            && empty
+                .iter()
+                .all(|x| self.board_squares[*x][y].piece.is_none())
+            && !safe.iter().any(|x| self.is_square_attacked((*x, y), color))
+    }
+
+    // Moves the rook when the king castles
     pub fn castling_moves(&mut self, piece: piece::Piece, i_2: usize, j_2: usize) {
-        // castling moves - king
         if piece.piece_type == piece::PieceType::King {
             let (short, long) = self.castling(piece.color);
             if short && i_2 == 6 {
@@ -323,20 +295,6 @@ impl Board {
                 self.board_squares[i_2 - 2][j_2].piece = None;
             }
         }
-        // castling moves - Rook
-        if piece.piece_type == piece::PieceType::Rook {
-            let (short, long) = self.castling(piece.color);
-            if short && i_2 == 5 {
-                self.board_squares[i_2 + 1][j_2].piece =
-                    Some(piece::Piece::new(piece::PieceType::King, piece.color));
-                self.board_squares[i_2 - 1][j_2].piece = None;
-            }
-            if long && i_2 == 3 {
-                self.board_squares[i_2 - 1][j_2].piece =
-                    Some(piece::Piece::new(piece::PieceType::King, piece.color));
-                self.board_squares[i_2 + 1][j_2].piece = None;
-            }
-        }
     }
 
     pub fn promotion(
@@ -357,27 +315,81 @@ impl Board {
         attacked
     }
 
+    // Whether a piece of the opponent of `player_color` attacks `loc`. Pawns
+    // only attack diagonally and pieces only see up to the first piece in
+    // their way
     pub fn is_square_attacked(&mut self, loc: (usize, usize), player_color: piece::Color) -> bool {
-        let mut attacked = false;
+        use piece::PieceType::*;
 
-        for i in 0..8 {
-            for j in 0..8 {
-                match self.board_squares[i][j].piece {
-                    Some(p) => {
-                        if p.color != player_color {
-                            if p.clone()
-                                .available_moves((i, j), self)
-                                .contains(&*vec![loc.0, loc.1])
-                            {
-                                attacked = true;
-                            }
-                        }
+        let enemy = |x: i32, y: i32, kinds: &[piece::PieceType]| -> bool {
+            if !(0..8).contains(&x) || !(0..8).contains(&y) {
+                return false;
+            }
+            match self.board_squares[x as usize][y as usize].piece {
+                Some(p) => p.color != player_color && kinds.contains(&p.piece_type),
+                None => false,
+            }
+        };
+        let (x, y) = (loc.0 as i32, loc.1 as i32);
+
+        // Enemy pawns attack from the rank in front of us
+        let forward = match player_color {
+            piece::Color::White => 1,
+            piece::Color::Black => -1,
+        };
+        if enemy(x - 1, y + forward, &[Pawn]) || enemy(x + 1, y + forward, &[Pawn]) {
+            return true;
+        }
+
+        let knight = [
+            (1, 2),
+            (2, 1),
+            (2, -1),
+            (1, -2),
+            (-1, -2),
+            (-2, -1),
+            (-2, 1),
+            (-1, 2),
+        ];
+        let king = [
+            (1, 0),
+            (1, 1),
+            (0, 1),
+            (-1, 1),
+            (-1, 0),
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+        ];
+        if knight
+            .iter()
+            .any(|(dx, dy)| enemy(x + dx, y + dy, &[Knight]))
+            || king.iter().any(|(dx, dy)| enemy(x + dx, y + dy, &[King]))
+        {
+            return true;
+        }
+
+        for (dx, dy) in king.iter() {
+            let kinds = if *dx == 0 || *dy == 0 {
+                [Rook, Queen]
+            } else {
+                [Bishop, Queen]
+            };
+
+            let (mut i, mut j) = (x + dx, y + dy);
+            while (0..8).contains(&i) && (0..8).contains(&j) {
+                if self.board_squares[i as usize][j as usize].piece.is_some() {
+                    if enemy(i, j, &kinds) {
+                        return true;
                     }
-                    None => {}
+                    break;
                 }
+                i += dx;
+                j += dy;
             }
         }
-        attacked
+
+        false
     }
 
     pub fn blocked_by_team(&self, loc: (usize, usize), player_color: piece::Color) -> bool {
@@ -0,0 +1,410 @@
+use crate::game::{Game, Outcome};
+use crate::piece::*;
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// The longest time or bonus a time control can give, a year. Anything longer
+// is a mistake or an attempt to overflow the clock as times are added up
+pub const MAX_TIME: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+// Where the clock gets the time from, so tests can drive it by hand
+pub trait TimeSource: Send {
+    // Time since some fixed point, never going backwards
+    fn now(&self) -> Duration;
+}
+
+pub struct RealTime {
+    start: Instant,
+}
+
+// A time source that only moves when told to. Clones share the same time
+#[derive(Clone, Default)]
+pub struct ManualTime {
+    now: Arc<Mutex<Duration>>,
+}
+
+// What happens to a player's time around each of their moves
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Timing {
+    SuddenDeath,
+    // Added after every move
+    Fischer(Duration),
+    // Time used is given back after every move, up to this much
+    Bronstein(Duration),
+    // The clock only starts running this long into every move
+    Delay(Duration),
+}
+
+// `time` is added when the stage starts. A stage with a move count ends
+// after that many moves, the last one repeats if it has one
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Stage {
+    pub moves: Option<u32>,
+    pub time: Duration,
+    pub timing: Timing,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TimeControl {
+    pub stages: Vec<Stage>,
+}
+
+pub struct Clock {
+    pub control: TimeControl,
+    source: Box<dyn TimeSource>,
+    // Indexed by color, not counting the move being thought about
+    remaining: [Duration; 2],
+    moves: [u32; 2],
+    stage: [usize; 2],
+    stage_moves: [u32; 2],
+    running: Option<Color>,
+    // When the running side's turn started
+    started: Duration,
+    flagged: Option<Color>,
+}
+
+impl RealTime {
+    pub fn new() -> RealTime {
+        RealTime {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for RealTime {
+    fn default() -> Self {
+        RealTime::new()
+    }
+}
+
+impl TimeSource for RealTime {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+impl ManualTime {
+    pub fn new() -> ManualTime {
+        ManualTime::default()
+    }
+
+    pub fn advance(&self, time: Duration) {
+        *self.now.lock().unwrap() += time;
+    }
+}
+
+impl TimeSource for ManualTime {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+}
+
+impl TimeControl {
+    pub fn new(stages: Vec<Stage>) -> TimeControl {
+        TimeControl { stages }
+    }
+
+    // A single stage for the whole game
+    pub fn single(time: Duration, timing: Timing) -> TimeControl {
+        TimeControl::new(vec![Stage {
+            moves: None,
+            time,
+            timing,
+        }])
+    }
+
+    // Reads the PGN TimeControl tag style, with times in seconds and stages
+    // separated by colons: "300" is sudden death, "180+2" a Fischer
+    // increment, "300+5d" a delay and "300+5b" Bronstein.
+    // "40/5400+30:1800+30" is 90 minutes for 40 moves then 30 more, with 30
+    // seconds added for every move
+    pub fn parse(text: &str) -> Option<TimeControl> {
+        let mut stages = Vec::new();
+
+        for stage in text.trim().split(':') {
+            let (moves, rest) = match stage.split_once('/') {
+                Some((moves, rest)) => (Some(moves.parse().ok().filter(|m| *m > 0)?), rest),
+                None => (None, stage),
+            };
+            let (time, bonus) = match rest.split_once('+') {
+                Some((time, bonus)) => (time, Some(bonus)),
+                None => (rest, None),
+            };
+
+            let timing = match bonus {
+                None => Timing::SuddenDeath,
+                Some(bonus) => match bonus.strip_suffix('d') {
+                    Some(delay) => Timing::Delay(seconds(delay)?),
+                    None => match bonus.strip_suffix('b') {
+                        Some(bronstein) => Timing::Bronstein(seconds(bronstein)?),
+                        None => Timing::Fischer(seconds(bonus)?),
+                    },
+                },
+            };
+
+            stages.push(Stage {
+                moves,
+                time: seconds(time)?,
+                timing,
+            });
+        }
+
+        // Only the last stage may go on for the rest of the game
+        if stages[..stages.len() - 1].iter().any(|s| s.moves.is_none()) {
+            return None;
+        }
+
+        Some(TimeControl::new(stages))
+    }
+}
+
+// None for anything that isn't a time up to MAX_TIME
+fn seconds(text: &str) -> Option<Duration> {
+    let seconds: f64 = text.parse().ok()?;
+
+    Duration::try_from_secs_f64(seconds)
+        .ok()
+        .filter(|time| *time <= MAX_TIME)
+}
+
+// Writes the form parse reads
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, stage) in self.stages.iter().enumerate() {
+            if i > 0 {
+                write!(f, ":")?;
+            }
+            if let Some(moves) = stage.moves {
+                write!(f, "{}/", moves)?;
+            }
+            write!(f, "{}", stage.time.as_secs_f64())?;
+
+            match stage.timing {
+                Timing::SuddenDeath => {}
+                Timing::Fischer(bonus) => write!(f, "+{}", bonus.as_secs_f64())?,
+                Timing::Bronstein(bonus) => write!(f, "+{}b", bonus.as_secs_f64())?,
+                Timing::Delay(bonus) => write!(f, "+{}d", bonus.as_secs_f64())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Clock {
+    // Both sides get the first stage's time, nothing runs until start
+    pub fn new<T: TimeSource + 'static>(control: TimeControl, source: T) -> Clock {
+        let time = control.stages.first().map_or(Duration::ZERO, |s| s.time);
+
+        Clock {
+            control,
+            source: Box::new(source),
+            remaining: [time; 2],
+            moves: [0; 2],
+            stage: [0; 2],
+            stage_moves: [0; 2],
+            running: None,
+            started: Duration::ZERO,
+            flagged: None,
+        }
+    }
+
+    // Starts `color`'s clock, stopping the other one without counting a move
+    pub fn start(&mut self, color: Color) {
+        self.pause();
+        if self.flagged.is_none() {
+            self.running = Some(color);
+            self.started = self.source.now();
+        }
+    }
+
+    // Stops both clocks, the time used so far in the move is charged. A
+    // delay starts over when the clock is started again
+    pub fn pause(&mut self) {
+        if let Some(color) = self.running.take() {
+            let (_, charged) = self.charge(color);
+            let remaining = &mut self.remaining[color as usize];
+
+            if charged >= *remaining {
+                *remaining = Duration::ZERO;
+                self.flagged = Some(color);
+            } else {
+                *remaining -= charged;
+            }
+        }
+    }
+
+    // The running side has made its move: its time is charged, the bonus
+    // added and the other side's clock started. False if the time had
+    // already run out, the clocks are stopped then
+    pub fn press(&mut self) -> bool {
+        self.press_with(None)
+    }
+
+    // The running side has moved on another clock, which has `reported` left
+    // for it after the move. That time is taken instead of this clock's, so
+    // the time the move took to get here isn't charged, but never more than
+    // `lag` over what this clock measured
+    pub fn press_remote(&mut self, reported: Duration, lag: Duration) -> bool {
+        self.press_with(Some((reported, lag)))
+    }
+
+    // Takes `color`'s time from another clock, up to `lag` more than this one
+    // has left for it
+    pub fn correct(&mut self, color: Color, reported: Duration, lag: Duration) {
+        let time = reported.min(self.remaining(color) + lag);
+        self.set_remaining(color, time);
+    }
+
+    // Sets `color`'s time left, counting the move in progress
+    pub fn set_remaining(&mut self, color: Color, time: Duration) {
+        let index = color as usize;
+
+        // The running move's time is charged on top of what's stored
+        self.remaining[index] = if self.running == Some(color) {
+            time + self.charge(color).1
+        } else {
+            time
+        };
+
+        if time > Duration::ZERO && self.flagged == Some(color) {
+            self.flagged = None;
+        }
+    }
+
+    // Time left for `color`, counting the move in progress
+    pub fn remaining(&self, color: Color) -> Duration {
+        let remaining = self.remaining[color as usize];
+
+        if self.running == Some(color) {
+            remaining.saturating_sub(self.charge(color).1)
+        } else {
+            remaining
+        }
+    }
+
+    // Time left before the clock starts running again with a delay, zero
+    // without one or once it has passed
+    pub fn delay_left(&self, color: Color) -> Duration {
+        match (self.running, self.stage(color).timing) {
+            (Some(running), Timing::Delay(delay)) if running == color => {
+                delay.saturating_sub(self.source.now() - self.started)
+            }
+            _ => Duration::ZERO,
+        }
+    }
+
+    pub fn running(&self) -> Option<Color> {
+        self.running
+    }
+
+    // Moves made by `color` so far
+    pub fn moves(&self, color: Color) -> u32 {
+        self.moves[color as usize]
+    }
+
+    // The side whose time has run out, checking the running clock too
+    pub fn flagged(&self) -> Option<Color> {
+        if self.flagged.is_some() {
+            return self.flagged;
+        }
+
+        self.running
+            .filter(|color| self.remaining(*color) == Duration::ZERO)
+    }
+
+    // The result when a side has run out of time. It's a draw instead of a
+    // loss when the opponent has no way to mate
+    pub fn outcome(&self, game: &Game) -> Option<Outcome> {
+        let flagged = self.flagged()?;
+
+        Some(if game.insufficient_material(flagged.other()) {
+            Outcome::Draw
+        } else {
+            Outcome::win_for(flagged.other())
+        })
+    }
+
+    fn press_with(&mut self, reported: Option<(Duration, Duration)>) -> bool {
+        let color = match self.running {
+            Some(color) => color,
+            None => return false,
+        };
+        let index = color as usize;
+
+        let (used, charged) = self.charge(color);
+        let in_time = charged < self.remaining[index];
+        if !in_time && reported.is_none() {
+            self.flag(color);
+            return false;
+        }
+
+        let stage = self.stage(color);
+        if in_time {
+            self.remaining[index] -= charged;
+
+            match stage.timing {
+                Timing::Fischer(bonus) => self.remaining[index] += bonus,
+                Timing::Bronstein(bonus) => self.remaining[index] += used.min(bonus),
+                Timing::SuddenDeath | Timing::Delay(_) => {}
+            }
+        } else {
+            self.remaining[index] = Duration::ZERO;
+        }
+
+        self.moves[index] += 1;
+        self.stage_moves[index] += 1;
+        if stage
+            .moves
+            .is_some_and(|moves| self.stage_moves[index] >= moves)
+        {
+            self.next_stage(color);
+        }
+
+        if let Some((reported, lag)) = reported {
+            self.remaining[index] = reported.min(self.remaining[index] + lag);
+
+            if self.remaining[index] == Duration::ZERO {
+                self.flag(color);
+                return false;
+            }
+        }
+
+        self.running = Some(color.other());
+        self.started = self.source.now();
+        true
+    }
+
+    fn flag(&mut self, color: Color) {
+        self.remaining[color as usize] = Duration::ZERO;
+        self.flagged = Some(color);
+        self.running = None;
+    }
+
+    // The stage `color` is playing in
+    pub fn stage(&self, color: Color) -> Stage {
+        let stages = &self.control.stages;
+        stages[self.stage[color as usize].min(stages.len() - 1)]
+    }
+
+    // The time the move in progress has taken and how much of it is charged
+    fn charge(&self, color: Color) -> (Duration, Duration) {
+        let used = self.source.now() - self.started;
+
+        let charged = match self.stage(color).timing {
+            Timing::Delay(delay) => used.saturating_sub(delay),
+            _ => used,
+        };
+        (used, charged)
+    }
+
+    // Adds the next stage's time, the last stage repeats
+    fn next_stage(&mut self, color: Color) {
+        let index = color as usize;
+
+        self.stage[index] = (self.stage[index] + 1).min(self.control.stages.len() - 1);
+        self.stage_moves[index] = 0;
+        self.remaining[index] += self.control.stages[self.stage[index]].time;
+    }
+}
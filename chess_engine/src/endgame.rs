@@ -0,0 +1,364 @@
+use crate::game::Game;
+use crate::piece::*;
+
+use std::sync::OnceLock;
+
+// Distance to mate for the side to move, counted in moves of the winning side
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DistanceToMate {
+    Win(u32),
+    // Loss(0) means the side to move is already checkmated
+    Loss(u32),
+    Draw,
+}
+
+// Tables are indexed by side to move, strong king, weak king and the strong
+// side's extra piece, with the strong side always seen as white. A value is
+// the number of plies to mate, or UNKNOWN if the position isn't won
+const SIZE: usize = 2 * 64 * 64 * 64;
+const UNKNOWN: u8 = u8::MAX;
+
+const STRONG: usize = 0;
+const WEAK: usize = 1;
+
+const KING_STEPS: [(i32, i32); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+const ROOK_STEPS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+struct Table {
+    kind: PieceType,
+    plies: Vec<u8>,
+}
+
+struct Tables {
+    queen: Table,
+    rook: Table,
+    pawn: Table,
+}
+
+static TABLES: OnceLock<Tables> = OnceLock::new();
+
+// The tables are generated the first time they're needed, which takes a
+// moment for all three together
+fn tables() -> &'static Tables {
+    TABLES.get_or_init(|| {
+        let queen = Table::generate(PieceType::Queen, &[]);
+        let rook = Table::generate(PieceType::Rook, &[]);
+        let pawn = Table::generate(PieceType::Pawn, &[&queen, &rook]);
+
+        Tables { queen, rook, pawn }
+    })
+}
+
+fn table(kind: PieceType) -> Option<&'static Table> {
+    match kind {
+        PieceType::Queen => Some(&tables().queen),
+        PieceType::Rook => Some(&tables().rook),
+        PieceType::Pawn => Some(&tables().pawn),
+        _ => None,
+    }
+}
+
+// Looks up KQK, KRK and KPK positions. Anything else, or a position that
+// can't occur in a game, gives None. Castling rights are ignored
+pub fn probe(game: &Game) -> Option<DistanceToMate> {
+    let mut kings = [None, None];
+    let mut extra = None;
+
+    for x in 0..8 {
+        for y in 0..8 {
+            if let Some(piece) = game.board.board_squares[x][y].piece {
+                if piece.piece_type == PieceType::King {
+                    kings[piece.color as usize] = Some((x, y));
+                } else if extra.replace((piece, (x, y))).is_some() {
+                    return None;
+                }
+            }
+        }
+    }
+
+    let (piece, at) = extra?;
+    let table = table(piece.piece_type)?;
+
+    // Flip the board when black is the one with the extra piece
+    let square = |(x, y): (usize, usize)| match piece.color {
+        Color::White => y * 8 + x,
+        Color::Black => (7 - y) * 8 + x,
+    };
+    let other = piece.color.other();
+
+    let sk = square(kings[piece.color as usize]?);
+    let wk = square(kings[other as usize]?);
+    let p = square(at);
+    let turn = if game.curr_player == piece.color {
+        STRONG
+    } else {
+        WEAK
+    };
+
+    if !legal(table.kind, turn, sk, wk, p) {
+        return None;
+    }
+
+    Some(match table.plies[index(turn, sk, wk, p)] {
+        UNKNOWN => DistanceToMate::Draw,
+        plies if turn == STRONG => DistanceToMate::Win((plies as u32).div_ceil(2)),
+        plies => DistanceToMate::Loss(plies as u32 / 2),
+    })
+}
+
+// Longest forced mate in the table, in moves, for the side with the piece
+pub fn longest_mate(kind: PieceType) -> Option<u32> {
+    let table = table(kind)?;
+
+    table.plies[..SIZE / 2]
+        .iter()
+        .filter(|plies| **plies != UNKNOWN)
+        .map(|plies| (*plies as u32).div_ceil(2))
+        .max()
+}
+
+impl Table {
+    // Retrograde analysis: start from the mates and walk moves backwards in
+    // order of distance. A strong side position is won as soon as one of its
+    // moves reaches a lost position, a weak side position is lost once every
+    // one of its moves reaches a won position. KPK also starts from the
+    // promotions into the tables in `promotions`
+    fn generate(kind: PieceType, promotions: &[&Table]) -> Table {
+        let mut plies = vec![UNKNOWN; SIZE];
+        let mut done = vec![false; SIZE];
+        let mut moves_left = vec![0u8; SIZE / 2];
+        let mut buckets: Vec<Vec<usize>> = Vec::new();
+
+        for sk in 0..64 {
+            for wk in 0..64 {
+                for p in 0..64 {
+                    if legal(kind, WEAK, sk, wk, p) {
+                        let (count, check) = weak_moves(kind, sk, wk, p);
+                        moves_left[index(WEAK, sk, wk, p) - SIZE / 2] = count;
+
+                        if count == 0 && check {
+                            plies[index(WEAK, sk, wk, p)] = 0;
+                            push(&mut buckets, 0, index(WEAK, sk, wk, p));
+                        }
+                    }
+
+                    if kind != PieceType::Pawn || p / 8 != 6 || !legal(kind, STRONG, sk, wk, p) {
+                        continue;
+                    }
+
+                    let promoted = p + 8;
+                    if promoted == sk || promoted == wk {
+                        continue;
+                    }
+
+                    let best = promotions
+                        .iter()
+                        .map(|table| table.plies[index(WEAK, sk, wk, promoted)])
+                        .filter(|plies| *plies != UNKNOWN)
+                        .min();
+
+                    if let Some(best) = best {
+                        let i = index(STRONG, sk, wk, p);
+                        plies[i] = plies[i].min(best + 1);
+                        push(&mut buckets, best as usize + 1, i);
+                    }
+                }
+            }
+        }
+
+        let mut distance = 0;
+        while distance < buckets.len() {
+            let bucket = std::mem::take(&mut buckets[distance]);
+
+            for i in bucket {
+                if done[i] || plies[i] as usize != distance {
+                    continue;
+                }
+                done[i] = true;
+
+                let (turn, sk, wk, p) =
+                    (i / (64 * 64 * 64), i / (64 * 64) % 64, i / 64 % 64, i % 64);
+                let next = distance as u8 + 1;
+
+                if turn == WEAK {
+                    for q in strong_unmoves(kind, sk, wk, p) {
+                        if plies[q] > next {
+                            plies[q] = next;
+                            push(&mut buckets, next as usize, q);
+                        }
+                    }
+                } else {
+                    for q in weak_unmoves(sk, wk, p) {
+                        let left = &mut moves_left[q - SIZE / 2];
+                        *left -= 1;
+
+                        if *left == 0 {
+                            plies[q] = next;
+                            push(&mut buckets, next as usize, q);
+                        }
+                    }
+                }
+            }
+
+            distance += 1;
+        }
+
+        Table { kind, plies }
+    }
+}
+
+fn push(buckets: &mut Vec<Vec<usize>>, plies: usize, i: usize) {
+    if buckets.len() <= plies {
+        buckets.resize(plies + 1, Vec::new());
+    }
+    buckets[plies].push(i);
+}
+
+fn index(turn: usize, sk: usize, wk: usize, p: usize) -> usize {
+    ((turn * 64 + sk) * 64 + wk) * 64 + p
+}
+
+fn step(square: usize, (dx, dy): (i32, i32)) -> Option<usize> {
+    let x = (square % 8) as i32 + dx;
+    let y = (square / 8) as i32 + dy;
+
+    if (0..8).contains(&x) && (0..8).contains(&y) {
+        Some((y * 8 + x) as usize)
+    } else {
+        None
+    }
+}
+
+fn adjacent(a: usize, b: usize) -> bool {
+    a != b && (a % 8).abs_diff(b % 8) <= 1 && (a / 8).abs_diff(b / 8) <= 1
+}
+
+// Does the strong piece on `p` attack `target`, with `blocker` in the way?
+fn attacks(kind: PieceType, p: usize, target: usize, blocker: usize) -> bool {
+    let (dx, dy) = (
+        (target % 8) as i32 - (p % 8) as i32,
+        (target / 8) as i32 - (p / 8) as i32,
+    );
+
+    let slides = match kind {
+        PieceType::Pawn => return dy == 1 && dx.abs() == 1,
+        PieceType::Rook => dx == 0 || dy == 0,
+        _ => dx == 0 || dy == 0 || dx.abs() == dy.abs(),
+    };
+
+    if !slides || p == target {
+        return false;
+    }
+
+    let direction = (dx.signum(), dy.signum());
+    let mut square = step(p, direction).unwrap();
+    while square != target {
+        if square == blocker {
+            return false;
+        }
+        square = step(square, direction).unwrap();
+    }
+
+    true
+}
+
+fn legal(kind: PieceType, turn: usize, sk: usize, wk: usize, p: usize) -> bool {
+    if sk == wk || sk == p || wk == p || adjacent(sk, wk) {
+        return false;
+    }
+
+    if kind == PieceType::Pawn && (p / 8 == 0 || p / 8 == 7) {
+        return false;
+    }
+
+    // The weak side can't have left its king in check
+    turn == WEAK || !attacks(kind, p, wk, sk)
+}
+
+// Number of legal weak king moves and whether it's in check. Taking an
+// unprotected piece counts as a move, it just leaves the table as a draw
+fn weak_moves(kind: PieceType, sk: usize, wk: usize, p: usize) -> (u8, bool) {
+    let mut count = 0;
+
+    for s in KING_STEPS.iter().filter_map(|d| step(wk, *d)) {
+        if s == sk || adjacent(s, sk) {
+            continue;
+        }
+
+        if s == p || !attacks(kind, p, s, sk) {
+            count += 1;
+        }
+    }
+
+    (count, attacks(kind, p, wk, sk))
+}
+
+// Weak side positions that reach the strong side position (sk, wk, p) with
+// one king move
+fn weak_unmoves(sk: usize, wk: usize, p: usize) -> Vec<usize> {
+    KING_STEPS
+        .iter()
+        .filter_map(|d| step(wk, *d))
+        .filter(|s| *s != sk && *s != p && !adjacent(*s, sk))
+        .map(|s| index(WEAK, sk, s, p))
+        .collect()
+}
+
+// Strong side positions that reach the weak side position (sk, wk, p) with
+// one move
+fn strong_unmoves(kind: PieceType, sk: usize, wk: usize, p: usize) -> Vec<usize> {
+    let mut positions = Vec::new();
+    let empty = |s: usize| s != sk && s != wk && s != p;
+
+    for s in KING_STEPS.iter().filter_map(|d| step(sk, *d)) {
+        if empty(s) && legal(kind, STRONG, s, wk, p) {
+            positions.push(index(STRONG, s, wk, p));
+        }
+    }
+
+    let mut froms = Vec::new();
+    match kind {
+        PieceType::Pawn => {
+            if p / 8 >= 2 && empty(p - 8) {
+                froms.push(p - 8);
+
+                if p / 8 == 3 && empty(p - 16) {
+                    froms.push(p - 16);
+                }
+            }
+        }
+        _ => {
+            // A queen slides in every direction a king can step
+            let directions = if kind == PieceType::Rook {
+                &ROOK_STEPS[..]
+            } else {
+                &KING_STEPS[..]
+            };
+
+            for direction in directions {
+                let mut square = step(p, *direction);
+                while let Some(s) = square.filter(|s| empty(*s)) {
+                    froms.push(s);
+                    square = step(s, *direction);
+                }
+            }
+        }
+    }
+
+    for s in froms {
+        if legal(kind, STRONG, sk, wk, s) {
+            positions.push(index(STRONG, sk, wk, s));
+        }
+    }
+
+    positions
+}
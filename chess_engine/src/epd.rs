@@ -0,0 +1,166 @@
+use crate::fen;
+use crate::game::Game;
+use crate::pgn::{self, PgnMove};
+use crate::uci::{Engine, Limit, UciMove};
+
+use std::io;
+use std::time::{Duration, Instant};
+
+// One position of a test suite. `fen` is completed with the hmvc and fmvn
+// operations, or zero and one when they're missing
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EpdRecord {
+    pub fen: String,
+    pub bm: Vec<String>,
+    pub am: Vec<String>,
+    pub id: Option<String>,
+    pub c0: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpdResult {
+    pub best: Option<UciMove>,
+    // The engine's move in SAN, None if it didn't give a legal one
+    pub san: Option<String>,
+    pub solved: bool,
+    pub time: Duration,
+}
+
+// Parses one EPD line, blank lines and lines starting with # give None
+pub fn parse_line(line: &str) -> Option<EpdRecord> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let mut fields = line.splitn(5, char::is_whitespace);
+    let position: Vec<&str> = fields.by_ref().take(4).collect();
+    if position.len() != 4 {
+        return None;
+    }
+
+    let mut record = EpdRecord::default();
+    let mut counters = ("0".to_owned(), "1".to_owned());
+
+    for (opcode, operands) in parse_operations(fields.next().unwrap_or("")) {
+        match opcode.as_str() {
+            "bm" => record.bm = operands,
+            "am" => record.am = operands,
+            "id" => record.id = operands.into_iter().next(),
+            "c0" => record.c0 = operands.into_iter().next(),
+            "hmvc" => counters.0 = operands.into_iter().next()?,
+            "fmvn" => counters.1 = operands.into_iter().next()?,
+            _ => {}
+        }
+    }
+
+    record.fen = format!("{} {} {}", position.join(" "), counters.0, counters.1);
+    fen::parse(&record.fen)?;
+
+    Some(record)
+}
+
+// Splits "bm Nf3 Nc3; id \"test 1\";" into opcodes and operands
+fn parse_operations(text: &str) -> Vec<(String, Vec<String>)> {
+    let mut operations = Vec::new();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+
+    for c in text.chars() {
+        match c {
+            '"' => {
+                if quoted {
+                    words.push(std::mem::take(&mut word));
+                }
+                quoted = !quoted;
+            }
+            _ if quoted => word.push(c),
+            ';' => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                if !words.is_empty() {
+                    let opcode = words.remove(0);
+                    operations.push((opcode, std::mem::take(&mut words)));
+                }
+            }
+            c if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+
+    // Be lenient about a missing semicolon after the last operation
+    if !word.is_empty() {
+        words.push(word);
+    }
+    if !words.is_empty() {
+        let opcode = words.remove(0);
+        operations.push((opcode, words));
+    }
+
+    operations
+}
+
+impl EpdRecord {
+    pub fn game(&self) -> Game {
+        fen::parse(&self.fen).unwrap()
+    }
+
+    // A move solves the position if it's one of the best moves and none of
+    // the moves to avoid. Moves that can't be read are skipped
+    pub fn solved_by(&self, mv: &UciMove) -> bool {
+        let game = self.game();
+        let matches = |sans: &[String]| {
+            sans.iter()
+                .filter_map(|san| pgn::parse_san(&game, san))
+                .any(|m| m.from == mv.from && m.to == mv.to && m.promotion == mv.promotion)
+        };
+
+        if !self.bm.is_empty() && !matches(&self.bm) {
+            return false;
+        }
+
+        !matches(&self.am)
+    }
+
+    // Has the engine search the position. `timeout` is how long to wait for
+    // it before giving up
+    pub fn solve(
+        &self,
+        engine: &mut Engine,
+        limit: Limit,
+        timeout: Duration,
+    ) -> io::Result<EpdResult> {
+        let start = Instant::now();
+
+        engine.new_game()?;
+        engine.position_fen(&self.fen, &[])?;
+        engine.go(limit)?;
+        let (best, _) = engine.finish_search(timeout)?;
+
+        let time = start.elapsed();
+        let san = best.and_then(|mv| {
+            pgn::to_san(
+                &self.game(),
+                &PgnMove {
+                    from: mv.from,
+                    to: mv.to,
+                    promotion: mv.promotion,
+                },
+            )
+        });
+        let solved = san.is_some() && self.solved_by(&best.unwrap());
+
+        Ok(EpdResult {
+            best,
+            san,
+            solved,
+            time,
+        })
+    }
+}
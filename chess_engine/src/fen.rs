@@ -0,0 +1,99 @@
+use crate::game::Game;
+use crate::piece::*;
+
+pub const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// Sets up a game from a FEN string, the move counters are optional. The
+// board only remembers which squares pieces have moved from and to, so a
+// missing castling right is recorded as its rook having moved. An en passant
+// square is recorded as the pawn's double step
+pub fn parse(fen: &str) -> Option<Game> {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    if fields.len() < 4 {
+        return None;
+    }
+
+    let mut game = Game::new();
+    game.board.history = vec![vec![]];
+    for column in game.board.board_squares.iter_mut() {
+        for square in column.iter_mut() {
+            square.piece = None;
+        }
+    }
+
+    let ranks: Vec<&str> = fields[0].split('/').collect();
+    if ranks.len() != 8 {
+        return None;
+    }
+
+    for (i, rank) in ranks.iter().enumerate() {
+        let y = 7 - i;
+        let mut x = 0;
+
+        for c in rank.chars() {
+            if let Some(empty) = c.to_digit(10) {
+                x += empty as usize;
+                continue;
+            }
+
+            if x >= 8 {
+                return None;
+            }
+
+            let color = if c.is_ascii_uppercase() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let kind = match c.to_ascii_lowercase() {
+                'p' => PieceType::Pawn,
+                'n' => PieceType::Knight,
+                'b' => PieceType::Bishop,
+                'r' => PieceType::Rook,
+                'q' => PieceType::Queen,
+                'k' => PieceType::King,
+                _ => return None,
+            };
+
+            game.board.board_squares[x][y].piece = Some(Piece::new(kind, color));
+            x += 1;
+        }
+
+        if x != 8 {
+            return None;
+        }
+    }
+
+    game.curr_player = match fields[1] {
+        "w" => Color::White,
+        "b" => Color::Black,
+        _ => return None,
+    };
+
+    let castling = fields[2];
+    if castling != "-" && !castling.chars().all(|c| "KQkq".contains(c)) {
+        return None;
+    }
+    for (right, rook) in [('K', [7, 0]), ('Q', [0, 0]), ('k', [7, 7]), ('q', [0, 7])] {
+        if !castling.contains(right) {
+            game.board.history.push(rook.to_vec());
+            game.board.history.push(rook.to_vec());
+        }
+    }
+
+    if fields[3] != "-" {
+        let bytes = fields[3].as_bytes();
+        let (x, rank) = match bytes {
+            [file @ b'a'..=b'h', rank @ (b'3' | b'6')] => ((file - b'a') as usize, *rank),
+            _ => return None,
+        };
+
+        // The pawn went from the second to the fourth rank or the seventh
+        // to the fifth
+        let (from, to) = if rank == b'3' { (1, 3) } else { (6, 4) };
+        game.board.history.push(vec![x, from]);
+        game.board.history.push(vec![x, to]);
+    }
+
+    Some(game)
+}
@@ -1,6 +1,7 @@
 use crate::board;
 use crate::piece::*;
 use std::collections::HashSet;
+use std::fmt;
 
 use std::io;
 
@@ -16,6 +17,14 @@ pub struct Game {
     pub promotable: Option<(usize, usize)>,
 }
 
+// How a finished game ended
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
 impl Game {
     pub fn new() -> Self {
         let mut game = Game {
@@ -98,6 +107,33 @@ impl Game {
         (checkmate, stalemate)
     }
 
+    // Whether `color` can't possibly mate: a bare king, or a king and a
+    // single knight or bishop against a bare king
+    pub fn insufficient_material(&self, color: Color) -> bool {
+        let mut minors = 0;
+        let mut opponent_pieces = 0;
+
+        for column in &self.board.board_squares {
+            for piece in column.iter().filter_map(|square| square.piece) {
+                if piece.piece_type == PieceType::King {
+                    continue;
+                }
+
+                if piece.color != color {
+                    opponent_pieces += 1;
+                    continue;
+                }
+
+                match piece.piece_type {
+                    PieceType::Knight | PieceType::Bishop => minors += 1,
+                    _ => return false,
+                }
+            }
+        }
+
+        minors == 0 || (minors == 1 && opponent_pieces == 0)
+    }
+
     pub fn init_game(&mut self) {
         self.board.init();
         self.board.display();
@@ -145,6 +181,33 @@ impl Game {
     }
 }
 
+impl Outcome {
+    pub fn flip(self) -> Outcome {
+        match self {
+            Outcome::WhiteWins => Outcome::BlackWins,
+            Outcome::BlackWins => Outcome::WhiteWins,
+            Outcome::Draw => Outcome::Draw,
+        }
+    }
+
+    pub fn win_for(color: Color) -> Outcome {
+        match color {
+            Color::White => Outcome::WhiteWins,
+            Color::Black => Outcome::BlackWins,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::WhiteWins => write!(f, "1-0"),
+            Outcome::BlackWins => write!(f, "0-1"),
+            Outcome::Draw => write!(f, "1/2-1/2"),
+        }
+    }
+}
+
 pub fn get_input() -> (char, char, Option<u32>) {
     let mut input = String::new();
 
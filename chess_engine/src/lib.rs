@@ -3,3 +3,27 @@ pub mod piece;
 pub mod game;
 
 pub mod board;
+
+pub mod endgame;
+
+pub mod pgn;
+
+pub mod polyglot;
+
+pub mod uci;
+
+pub mod review;
+
+pub mod see;
+
+pub mod fen;
+
+pub mod epd;
+
+pub mod arena;
+
+pub mod sprt;
+
+pub mod mate;
+
+pub mod clock;
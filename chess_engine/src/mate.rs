@@ -0,0 +1,224 @@
+use crate::game::Game;
+use crate::pgn::{self, PgnMove};
+use crate::piece::*;
+use crate::uci::{self, UciMove};
+
+use std::fmt;
+
+const PROMOTIONS: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+// A move by the side giving mate and every defence to it. The defences are
+// empty when the move mates
+#[derive(Clone, Debug, PartialEq)]
+pub struct MateLine {
+    pub mv: UciMove,
+    pub san: String,
+    pub defences: Vec<Defence>,
+}
+
+// A move by the defending side and every move that still mates in time
+// after it, so duals show up as more than one reply
+#[derive(Clone, Debug, PartialEq)]
+pub struct Defence {
+    pub mv: UciMove,
+    pub san: String,
+    pub replies: Vec<MateLine>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Solution {
+    pub moves: usize,
+    // Every first move that forces mate in `moves` moves or fewer
+    pub keys: Vec<MateLine>,
+    // The fewest moves mate can be forced in, None if it can't be
+    pub shortest: Option<usize>,
+}
+
+// Proves or disproves that the side to move, normally white, mates in `n`
+// moves against any defence. The search is exhaustive over the moves Game
+// allows, stalemate counts as a successful defence
+pub fn solve(game: &Game, n: usize) -> Solution {
+    let shortest = (1..=n).find(|m| forces_mate(game, *m));
+
+    let keys = match shortest {
+        Some(_) => legal_moves(game)
+            .into_iter()
+            .filter(|mv| defender_loses(&after(game, mv), n))
+            .map(|mv| line(game, &mv, n))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    Solution {
+        moves: n,
+        keys,
+        shortest,
+    }
+}
+
+impl Solution {
+    pub fn is_solved(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    // Keys other than the one the composer intended
+    pub fn cooks(&self, intended: &UciMove) -> Vec<&MateLine> {
+        self.keys.iter().filter(|key| key.mv != *intended).collect()
+    }
+}
+
+// One line per move, defences indented under the move they answer
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.keys.is_empty() {
+            return writeln!(f, "no mate in {}", self.moves);
+        }
+
+        for key in &self.keys {
+            write_line(f, key, 1, 0)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_line(f: &mut fmt::Formatter, line: &MateLine, number: usize, depth: usize) -> fmt::Result {
+    writeln!(f, "{}{}. {}", "    ".repeat(depth), number, line.san)?;
+
+    for defence in &line.defences {
+        writeln!(
+            f,
+            "{}{}... {}",
+            "    ".repeat(depth + 1),
+            number,
+            defence.san
+        )?;
+        for reply in &defence.replies {
+            write_line(f, reply, number + 1, depth + 2)?;
+        }
+    }
+    Ok(())
+}
+
+// Whether the side to move has a move after which every defence gets mated
+// within `n` moves
+fn forces_mate(game: &Game, n: usize) -> bool {
+    legal_moves(game)
+        .iter()
+        .any(|mv| defender_loses(&after(game, mv), n))
+}
+
+// Whether the side to move, having just been moved against, is mated now or
+// gets mated within `n - 1` more moves whatever it plays
+fn defender_loses(game: &Game, n: usize) -> bool {
+    let in_check = game.board.clone().is_king_attacked(game.curr_player);
+
+    // Only a check can mate on the last move
+    if n == 1 && !in_check {
+        return false;
+    }
+
+    let defences = legal_moves(game);
+    if defences.is_empty() {
+        return in_check;
+    }
+    if n == 1 {
+        return false;
+    }
+
+    defences
+        .iter()
+        .all(|defence| forces_mate(&after(game, defence), n - 1))
+}
+
+// The tree below `mv`, which is known to mate within `n` moves
+fn line(game: &Game, mv: &UciMove, n: usize) -> MateLine {
+    let next = after(game, mv);
+
+    let defences = legal_moves(&next)
+        .into_iter()
+        .map(|defence| {
+            let position = after(&next, &defence);
+            let replies = legal_moves(&position)
+                .into_iter()
+                .filter(|reply| defender_loses(&after(&position, reply), n - 1))
+                .map(|reply| line(&position, &reply, n - 1))
+                .collect();
+
+            Defence {
+                mv: defence,
+                san: san(&next, &defence),
+                replies,
+            }
+        })
+        .collect();
+
+    MateLine {
+        mv: *mv,
+        san: san(game, mv),
+        defences,
+    }
+}
+
+// Moves for the side to move in a fixed order, with a move for every
+// promotion piece
+fn legal_moves(game: &Game) -> Vec<UciMove> {
+    let mut game = game.clone();
+    let mut moves = Vec::new();
+
+    for x in 0..8 {
+        for y in 0..8 {
+            let piece = match game.board.board_squares[x][y].piece {
+                Some(piece) if piece.color == game.curr_player => piece,
+                _ => continue,
+            };
+
+            let mut targets: Vec<(usize, usize)> = game
+                .get_available_moves((x, y))
+                .into_iter()
+                .map(|to| (to[0], to[1]))
+                .collect();
+            targets.sort_unstable();
+
+            for to in targets {
+                if piece.piece_type == PieceType::Pawn && (to.1 == 0 || to.1 == 7) {
+                    moves.extend(PROMOTIONS.iter().map(|kind| UciMove {
+                        from: (x, y),
+                        to,
+                        promotion: Some(*kind),
+                    }));
+                } else {
+                    moves.push(UciMove {
+                        from: (x, y),
+                        to,
+                        promotion: None,
+                    });
+                }
+            }
+        }
+    }
+
+    moves
+}
+
+fn after(game: &Game, mv: &UciMove) -> Game {
+    let mut game = game.clone();
+    uci::play_move(&mut game, mv);
+    game
+}
+
+fn san(game: &Game, mv: &UciMove) -> String {
+    pgn::to_san(
+        game,
+        &PgnMove {
+            from: mv.from,
+            to: mv.to,
+            promotion: mv.promotion,
+        },
+    )
+    .unwrap_or_else(|| mv.to_string())
+}
@@ -0,0 +1,379 @@
+use crate::game::Game;
+use crate::piece::*;
+
+#[derive(Clone, Debug, Default)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    pub result: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PgnMove {
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    pub promotion: Option<PieceType>,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.moves.is_empty() && self.result.is_none()
+    }
+}
+
+// Splits a PGN file into games. Comments, NAGs and variations are dropped,
+// only the mainline SAN moves are kept
+pub fn parse(text: &str) -> Vec<PgnGame> {
+    let mut games = Vec::new();
+    let mut current = PgnGame::default();
+    let mut chars = text.chars().peekable();
+    let mut depth = 0;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' if depth == 0 => {
+                if !current.moves.is_empty() {
+                    games.push(std::mem::take(&mut current));
+                }
+
+                let mut line = String::new();
+                let mut quoted = false;
+                let mut escaped = false;
+                for c in chars.by_ref() {
+                    if c == ']' && !quoted {
+                        break;
+                    }
+                    if c == '"' && !escaped {
+                        quoted = !quoted;
+                    }
+                    escaped = c == '\\' && !escaped;
+                    line.push(c);
+                }
+
+                if let Some(tag) = parse_tag(&line) {
+                    current.tags.push(tag);
+                }
+            }
+            '{' => {
+                chars.by_ref().find(|c| *c == '}');
+            }
+            ';' => {
+                chars.by_ref().find(|c| *c == '\n');
+            }
+            '(' => depth += 1,
+            ')' => depth = std::cmp::max(depth, 1) - 1,
+            c if c.is_whitespace() => {}
+            c => {
+                let mut token = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "{}()[];".contains(next) {
+                        break;
+                    }
+                    token.push(next);
+                    chars.next();
+                }
+
+                if depth > 0 || token.starts_with('$') {
+                    continue;
+                }
+
+                if let "1-0" | "0-1" | "1/2-1/2" | "*" = token.as_str() {
+                    current.result = Some(token);
+                    games.push(std::mem::take(&mut current));
+                    continue;
+                }
+
+                // Move numbers can be glued to the move, "12.e4" or "12...e5"
+                let mut san = token.as_str();
+                let rest = san.trim_start_matches(|c: char| c.is_ascii_digit());
+                if rest.starts_with('.') {
+                    san = rest.trim_start_matches('.');
+                }
+
+                let san = san.trim_end_matches(['!', '?']);
+                if !san.is_empty() {
+                    current.moves.push(san.to_owned());
+                }
+            }
+        }
+    }
+
+    if !current.is_empty() {
+        games.push(current);
+    }
+
+    games
+}
+
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    let split = line.find(char::is_whitespace)?;
+    let (name, value) = line.split_at(split);
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    Some((
+        name.to_owned(),
+        value.replace("\\\"", "\"").replace("\\\\", "\\"),
+    ))
+}
+
+// Finds the move described by `san` in the current position
+pub fn parse_san(game: &Game, san: &str) -> Option<PgnMove> {
+    let san = san.trim_end_matches(|c| "+#!?".contains(c));
+    let rank = match game.curr_player {
+        Color::White => 0,
+        Color::Black => 7,
+    };
+
+    let castle = match san {
+        "O-O" | "0-0" => Some(6),
+        "O-O-O" | "0-0-0" => Some(2),
+        _ => None,
+    };
+
+    if let Some(x) = castle {
+        let mv = PgnMove {
+            from: (4, rank),
+            to: (x, rank),
+            promotion: None,
+        };
+        let mut game = game.clone();
+
+        return match game.board.board_squares[4][rank].piece {
+            Some(p) if p.piece_type == PieceType::King && p.color == game.curr_player => {
+                if game.get_available_moves(mv.from).contains(&vec![x, rank]) {
+                    Some(mv)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+    }
+
+    let (san, promotion) = match san.find('=') {
+        Some(i) => (&san[..i], Some(parse_piece(san[i + 1..].chars().next()?)?)),
+        None => match san.chars().last() {
+            Some(c) if c.is_ascii_uppercase() && san.starts_with(|c: char| c.is_lowercase()) => {
+                (&san[..san.len() - 1], Some(parse_piece(c)?))
+            }
+            _ => (san, None),
+        },
+    };
+
+    let (kind, rest) = match san.chars().next()? {
+        c if c.is_ascii_uppercase() => (parse_piece(c)?, &san[1..]),
+        _ => (PieceType::Pawn, san),
+    };
+
+    let rest: Vec<char> = rest.chars().filter(|c| *c != 'x').collect();
+    if rest.len() < 2 {
+        return None;
+    }
+
+    let to = parse_square(rest[rest.len() - 2], rest[rest.len() - 1])?;
+    let mut file = None;
+    let mut row = None;
+    for c in &rest[..rest.len() - 2] {
+        match c {
+            'a'..='h' => file = Some(*c as usize - 'a' as usize),
+            '1'..='8' => row = Some(*c as usize - '1' as usize),
+            _ => return None,
+        }
+    }
+
+    let mut game = game.clone();
+    let mut candidates = Vec::new();
+    for x in 0..8 {
+        for y in 0..8 {
+            if file.is_some_and(|f| f != x) || row.is_some_and(|r| r != y) {
+                continue;
+            }
+
+            match game.board.board_squares[x][y].piece {
+                Some(p) if p.piece_type == kind && p.color == game.curr_player => {}
+                _ => continue,
+            }
+
+            if game.get_available_moves((x, y)).contains(&vec![to.0, to.1]) {
+                candidates.push((x, y));
+            }
+        }
+    }
+
+    if candidates.len() != 1 {
+        return None;
+    }
+
+    Some(PgnMove {
+        from: candidates[0],
+        to,
+        promotion,
+    })
+}
+
+// Plays `san` on the game, returns the move or None if it isn't legal
+pub fn play_san(game: &mut Game, san: &str) -> Option<PgnMove> {
+    let mv = parse_san(game, san)?;
+
+    game.move_piece(mv.from, mv.to);
+    if let Some(kind) = mv.promotion {
+        game.promote(kind);
+    }
+
+    Some(mv)
+}
+
+// Writes a game from its tags and movetext tokens, which should end with
+// the result. Lines are kept under 80 characters
+pub fn write(tags: &[(String, String)], movetext: &[String]) -> String {
+    let mut text = String::new();
+    for (name, value) in tags {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        text.push_str(&format!("[{} \"{}\"]\n", name, value));
+    }
+    text.push('\n');
+
+    let mut line = String::new();
+    for token in movetext {
+        if !line.is_empty() && line.len() + token.len() >= 80 {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(token);
+    }
+    text.push_str(&line);
+    text.push('\n');
+
+    text
+}
+
+// Writes `mv` in SAN for the current position, None if it isn't legal
+pub fn to_san(game: &Game, mv: &PgnMove) -> Option<String> {
+    let mut game = game.clone();
+    let piece = game.board.board_squares[mv.from.0][mv.from.1].piece?;
+    if piece.color != game.curr_player
+        || !game
+            .get_available_moves(mv.from)
+            .contains(&vec![mv.to.0, mv.to.1])
+    {
+        return None;
+    }
+
+    let capture = game.board.board_squares[mv.to.0][mv.to.1].piece.is_some()
+        || (piece.piece_type == PieceType::Pawn && mv.from.0 != mv.to.0);
+    let mut san = String::new();
+
+    if piece.piece_type == PieceType::King && mv.from.0.abs_diff(mv.to.0) == 2 {
+        san.push_str(if mv.to.0 == 6 { "O-O" } else { "O-O-O" });
+    } else {
+        if piece.piece_type == PieceType::Pawn {
+            if capture {
+                san.push(file_char(mv.from.0));
+            }
+        } else {
+            san.push(piece_char(piece.piece_type));
+
+            let mut others = Vec::new();
+            for x in 0..8 {
+                for y in 0..8 {
+                    match game.board.board_squares[x][y].piece {
+                        Some(p) if p.piece_type == piece.piece_type && p.color == piece.color => {}
+                        _ => continue,
+                    }
+
+                    if (x, y) == mv.from {
+                        continue;
+                    }
+
+                    if game
+                        .get_available_moves((x, y))
+                        .contains(&vec![mv.to.0, mv.to.1])
+                    {
+                        others.push((x, y));
+                    }
+                }
+            }
+
+            if !others.is_empty() {
+                if others.iter().all(|(x, _)| *x != mv.from.0) {
+                    san.push(file_char(mv.from.0));
+                } else if others.iter().all(|(_, y)| *y != mv.from.1) {
+                    san.push(rank_char(mv.from.1));
+                } else {
+                    san.push(file_char(mv.from.0));
+                    san.push(rank_char(mv.from.1));
+                }
+            }
+        }
+
+        if capture {
+            san.push('x');
+        }
+        san.push(file_char(mv.to.0));
+        san.push(rank_char(mv.to.1));
+    }
+
+    game.move_piece(mv.from, mv.to);
+    if game.promotable.is_some() {
+        let kind = mv.promotion.unwrap_or(PieceType::Queen);
+
+        game.promote(kind);
+        san.push('=');
+        san.push(piece_char(kind));
+    }
+
+    if game.board.is_king_attacked(game.curr_player) {
+        san.push(if game.check_for_win().0 { '#' } else { '+' });
+    }
+
+    Some(san)
+}
+
+fn piece_char(kind: PieceType) -> char {
+    match kind {
+        PieceType::Knight => 'N',
+        PieceType::Bishop => 'B',
+        PieceType::Rook => 'R',
+        PieceType::Queen => 'Q',
+        PieceType::King => 'K',
+        PieceType::Pawn => 'P',
+    }
+}
+
+fn file_char(x: usize) -> char {
+    (b'a' + x as u8) as char
+}
+
+fn rank_char(y: usize) -> char {
+    (b'1' + y as u8) as char
+}
+
+fn parse_piece(c: char) -> Option<PieceType> {
+    match c {
+        'N' => Some(PieceType::Knight),
+        'B' => Some(PieceType::Bishop),
+        'R' => Some(PieceType::Rook),
+        'Q' => Some(PieceType::Queen),
+        'K' => Some(PieceType::King),
+        _ => None,
+    }
+}
+
+fn parse_square(file: char, row: char) -> Option<(usize, usize)> {
+    match (file, row) {
+        ('a'..='h', '1'..='8') => Some((file as usize - 'a' as usize, row as usize - '1' as usize)),
+        _ => None,
+    }
+}
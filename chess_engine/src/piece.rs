@@ -109,10 +109,12 @@ impl Piece {
         ];
         let mut available_moves = self.generate_moves(steps, loc, board);
 
-        if loc.1 == 1 && self.color == Color::White {
+        // The double step needs both squares in front to be empty
+        let empty = |y: usize| board.board_squares[loc.0][y].piece.is_none();
+        if loc.1 == 1 && self.color == Color::White && empty(2) && empty(3) {
             available_moves.insert([loc.0, loc.1 + 2].to_vec());
         }
-        if loc.1 == 6 && self.color == Color::Black {
+        if loc.1 == 6 && self.color == Color::Black && empty(5) && empty(4) {
             available_moves.insert([loc.0, loc.1 - 2].to_vec());
         }
         available_moves = self.check_for_en_passant(available_moves.clone(), loc, board);
@@ -138,7 +140,7 @@ impl Piece {
                 && board
                     .clone()
                     .blocked_by_enemy((loc.0 - 1, loc.1), self.clone().color)
-                && board.history[board.history.len() - 1] == vec![loc.0 - 1, loc.1]
+                && board.history.last() == Some(&vec![loc.0 - 1, loc.1])
             {
                 available_moves.insert([loc.0 - 1, col].to_vec());
                 board.board_squares[loc.0][loc.1].piece = Some(Piece {
@@ -152,7 +154,7 @@ impl Piece {
                 && board
                     .clone()
                     .blocked_by_enemy((loc.0 + 1, loc.1), self.clone().color)
-                && board.history[board.history.len() - 2] == vec![loc.0 + 1, loc.1]
+                && board.history.iter().rev().nth(1) == Some(&vec![loc.0 + 1, loc.1])
             {
                 available_moves.insert([loc.0 + 1, col].to_vec());
                 match board.board_squares[loc.0][loc.1].piece {
@@ -296,3 +298,12 @@ pub enum Color {
     White,
     Black,
 }
+
+impl Color {
+    pub fn other(self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
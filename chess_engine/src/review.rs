@@ -0,0 +1,232 @@
+use crate::game::Game;
+use crate::pgn::{self, PgnMove};
+use crate::piece::*;
+use crate::uci::{self, Engine, Limit, Score, UciMove};
+
+use std::io;
+use std::time::Duration;
+
+// Centipawn loss needed for each judgement
+const INACCURACY: i32 = 50;
+const MISTAKE: i32 = 100;
+const BLUNDER: i32 = 300;
+
+// Evaluations are capped before comparing them, dropping from +15 to +8 in
+// a won position isn't a mistake
+const MAX_EVAL: i32 = 1000;
+const MATE: i32 = 10000;
+
+const EVALUATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Judgement {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveReview {
+    pub mv: UciMove,
+    pub san: String,
+    pub color: Color,
+    // Evaluations in centipawns from the mover's side, before and after
+    pub before: i32,
+    pub after: i32,
+    pub loss: i32,
+    pub judgement: Option<Judgement>,
+    pub best: Option<UciMove>,
+    pub best_san: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Review {
+    pub moves: Vec<MoveReview>,
+    // Indexed by color, 0 to 100
+    pub accuracy: [f64; 2],
+}
+
+impl Judgement {
+    pub fn from_loss(loss: i32) -> Option<Judgement> {
+        match loss {
+            l if l >= BLUNDER => Some(Judgement::Blunder),
+            l if l >= MISTAKE => Some(Judgement::Mistake),
+            l if l >= INACCURACY => Some(Judgement::Inaccuracy),
+            _ => None,
+        }
+    }
+
+    pub fn nag(self) -> u8 {
+        match self {
+            Judgement::Inaccuracy => 6,
+            Judgement::Mistake => 2,
+            Judgement::Blunder => 4,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Judgement::Inaccuracy => "Inaccuracy",
+            Judgement::Mistake => "Mistake",
+            Judgement::Blunder => "Blunder",
+        }
+    }
+}
+
+// Evaluates every position of the game played from the start position with
+// `moves`. The limit has to end the search on its own, so not Infinite
+pub fn review(engine: &mut Engine, moves: &[UciMove], limit: Limit) -> io::Result<Review> {
+    let mut game = Game::new();
+    let (mut eval, mut best) = evaluate(engine, &[], limit)?;
+    let mut reviews = Vec::new();
+
+    for (i, mv) in moves.iter().enumerate() {
+        let color = game.curr_player;
+        let pgn_move = PgnMove {
+            from: mv.from,
+            to: mv.to,
+            promotion: mv.promotion,
+        };
+        let illegal =
+            || io::Error::new(io::ErrorKind::InvalidInput, format!("illegal move {}", mv));
+
+        let san = pgn::to_san(&game, &pgn_move).ok_or_else(illegal)?;
+        let best_san = best.and_then(|best| {
+            pgn::to_san(
+                &game,
+                &PgnMove {
+                    from: best.from,
+                    to: best.to,
+                    promotion: best.promotion,
+                },
+            )
+        });
+
+        if !uci::play_move(&mut game, mv) {
+            return Err(illegal());
+        }
+
+        let (next_eval, next_best) = evaluate(engine, &moves[..=i], limit)?;
+        let (before, after) = (eval, -next_eval);
+
+        let loss = if best == Some(*mv) {
+            0
+        } else {
+            (cap(before) - cap(after)).max(0)
+        };
+
+        reviews.push(MoveReview {
+            mv: *mv,
+            san,
+            color,
+            before,
+            after,
+            loss,
+            judgement: Judgement::from_loss(loss),
+            best,
+            best_san,
+        });
+
+        eval = next_eval;
+        best = next_best;
+    }
+
+    let accuracy = [Color::White, Color::Black].map(|color| {
+        let accuracies: Vec<f64> = reviews
+            .iter()
+            .filter(|r| r.color == color)
+            .map(|r| move_accuracy(r.before, r.after))
+            .collect();
+
+        if accuracies.is_empty() {
+            100.0
+        } else {
+            accuracies.iter().sum::<f64>() / accuracies.len() as f64
+        }
+    });
+
+    Ok(Review {
+        moves: reviews,
+        accuracy,
+    })
+}
+
+impl Review {
+    // Annotated PGN with NAGs on the bad moves and the better move in a
+    // comment after them
+    pub fn to_pgn(&self, tags: &[(String, String)], result: &str) -> String {
+        let mut tokens = vec![format!(
+            "{{Accuracy: White {:.1}%, Black {:.1}%}}",
+            self.accuracy[Color::White as usize],
+            self.accuracy[Color::Black as usize]
+        )];
+        let mut commented = true;
+
+        for (i, review) in self.moves.iter().enumerate() {
+            let number = i / 2 + 1;
+            if review.color == Color::White {
+                tokens.push(format!("{}.", number));
+            } else if commented {
+                tokens.push(format!("{}...", number));
+            }
+
+            tokens.push(review.san.clone());
+            commented = false;
+
+            if let Some(judgement) = review.judgement {
+                tokens.push(format!("${}", judgement.nag()));
+
+                let comment = match &review.best_san {
+                    Some(best) => format!("{{{}. {} was best.}}", judgement.name(), best),
+                    None => format!("{{{}.}}", judgement.name()),
+                };
+                tokens.push(comment);
+                commented = true;
+            }
+        }
+        tokens.push(result.to_owned());
+
+        pgn::write(tags, &tokens)
+    }
+}
+
+// Searches the position after `moves`, giving the evaluation for the side to
+// move and the engine's best move
+fn evaluate(
+    engine: &mut Engine,
+    moves: &[UciMove],
+    limit: Limit,
+) -> io::Result<(i32, Option<UciMove>)> {
+    engine.position(moves)?;
+    engine.go(limit)?;
+
+    let (best, info) = engine.finish_search(EVALUATION_TIMEOUT)?;
+    let score = info.and_then(|info| info.score);
+
+    Ok((score.map(centipawns).unwrap_or(0), best))
+}
+
+// Mates count as 100 pawns less the moves needed to give them
+pub fn centipawns(score: Score) -> i32 {
+    match score {
+        Score::Centipawns(cp) => cp,
+        Score::Mate(moves) if moves > 0 => MATE - moves,
+        Score::Mate(moves) => -MATE - moves,
+    }
+}
+
+fn cap(eval: i32) -> i32 {
+    eval.clamp(-MAX_EVAL, MAX_EVAL)
+}
+
+// Chance of winning from an evaluation, 0 to 100
+pub fn win_chance(eval: i32) -> f64 {
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * cap(eval) as f64).exp()) - 1.0)
+}
+
+// Accuracy of a single move from how much winning chance it gave away
+fn move_accuracy(before: i32, after: i32) -> f64 {
+    let lost = (win_chance(before) - win_chance(after)).max(0.0);
+
+    (103.1668 * (-0.04354 * lost).exp() - 3.1669).clamp(0.0, 100.0)
+}
@@ -0,0 +1,251 @@
+use crate::game::Game;
+use crate::piece::*;
+use crate::uci::UciMove;
+
+const KNIGHT_STEPS: [(i32, i32); 8] = [
+    (-2, -1),
+    (-2, 1),
+    (-1, -2),
+    (-1, 2),
+    (1, -2),
+    (1, 2),
+    (2, -1),
+    (2, 1),
+];
+const KING_STEPS: [(i32, i32); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+const DIAGONALS: [(i32, i32); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+const ORTHOGONALS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+pub fn value(kind: PieceType) -> i32 {
+    match kind {
+        PieceType::Pawn => 100,
+        PieceType::Knight => 300,
+        PieceType::Bishop => 300,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King => 20000,
+    }
+}
+
+impl Game {
+    // Static exchange evaluation: the material the side playing `mv` wins
+    // when both sides keep recapturing on its square with their least
+    // valuable piece for as long as it pays. Pieces uncovered behind an
+    // attacker join in. Pins and promotions are ignored and the move isn't
+    // checked for legality
+    pub fn see(&self, mv: &UciMove) -> i32 {
+        let to = mv.to;
+        let (mover, captured, mut occupied) = match self.exchange(mv.from, to) {
+            Some(exchange) => exchange,
+            None => return 0,
+        };
+
+        let mut gains = vec![captured];
+        let mut on_square = value(mover.piece_type);
+        let mut color = mover.color.other();
+
+        while let Some((square, kind)) = self.least_attacker(to, color, occupied) {
+            // A king can only take last
+            if kind == PieceType::King
+                && self
+                    .least_attacker(to, color.other(), occupied & !bit(square))
+                    .is_some()
+            {
+                break;
+            }
+
+            gains.push(on_square - gains[gains.len() - 1]);
+            on_square = value(kind);
+            occupied &= !bit(square);
+            color = color.other();
+        }
+
+        // Either side can stop capturing when going on would lose more
+        while gains.len() > 1 {
+            let last = gains.pop().unwrap();
+            let previous = gains.last_mut().unwrap();
+            *previous = -std::cmp::max(-*previous, last);
+        }
+
+        gains[0]
+    }
+
+    // Same as see(mv) >= threshold, but gives up on the exchange as soon as
+    // the answer is known
+    pub fn see_ge(&self, mv: &UciMove, threshold: i32) -> bool {
+        let to = mv.to;
+        let (mover, captured, mut occupied) = match self.exchange(mv.from, to) {
+            Some(exchange) => exchange,
+            None => return threshold <= 0,
+        };
+
+        // What the mover is ahead by, over the threshold, if the opponent
+        // doesn't recapture
+        let mut swap = captured - threshold;
+        if swap < 0 {
+            return false;
+        }
+
+        // And if the opponent gets the moved piece for free
+        swap = value(mover.piece_type) - swap;
+        if swap <= 0 {
+            return true;
+        }
+
+        // `passed` is the result if the side whose turn it is stops
+        let mut color = mover.color;
+        let mut passed = true;
+
+        loop {
+            color = color.other();
+            let (square, kind) = match self.least_attacker(to, color, occupied) {
+                Some(attacker) => attacker,
+                None => break,
+            };
+
+            passed = !passed;
+
+            if kind == PieceType::King {
+                let defended = self
+                    .least_attacker(to, color.other(), occupied & !bit(square))
+                    .is_some();
+
+                return if defended { !passed } else { passed };
+            }
+
+            swap = value(kind) - swap;
+            if swap < passed as i32 {
+                break;
+            }
+
+            occupied &= !bit(square);
+        }
+
+        passed
+    }
+
+    // The moving piece, the value it captures and the occupied squares once
+    // it has left `from`. None when there's no piece or it's castling
+    fn exchange(&self, from: (usize, usize), to: (usize, usize)) -> Option<(Piece, i32, u64)> {
+        let mover = self.board.board_squares[from.0][from.1].piece?;
+        if mover.piece_type == PieceType::King && from.0.abs_diff(to.0) == 2 {
+            return None;
+        }
+
+        let mut occupied = 0;
+        for x in 0..8 {
+            for y in 0..8 {
+                if self.board.board_squares[x][y].piece.is_some() {
+                    occupied |= bit((x, y));
+                }
+            }
+        }
+        occupied &= !bit(from);
+
+        let captured = match self.board.board_squares[to.0][to.1].piece {
+            Some(piece) => value(piece.piece_type),
+            // En passant takes the pawn beside the mover
+            None if mover.piece_type == PieceType::Pawn && from.0 != to.0 => {
+                occupied &= !bit((to.0, from.1));
+                value(PieceType::Pawn)
+            }
+            None => 0,
+        };
+
+        Some((mover, captured, occupied))
+    }
+
+    // Cheapest piece of `color` among `occupied` that attacks `target`
+    fn least_attacker(
+        &self,
+        target: (usize, usize),
+        color: Color,
+        occupied: u64,
+    ) -> Option<((usize, usize), PieceType)> {
+        let piece_at = |square: (usize, usize)| {
+            if occupied & bit(square) == 0 {
+                return None;
+            }
+            self.board.board_squares[square.0][square.1]
+                .piece
+                .filter(|piece| piece.color == color)
+                .map(|piece| piece.piece_type)
+        };
+
+        let mut best: Option<((usize, usize), PieceType)> = None;
+        let mut consider = |square: (usize, usize), kind: PieceType, kinds: &[PieceType]| {
+            if kinds.contains(&kind) && best.is_none_or(|(_, b)| value(kind) < value(b)) {
+                best = Some((square, kind));
+            }
+        };
+
+        // Pawns attack the target from the rank behind it
+        let behind = match color {
+            Color::White => -1,
+            Color::Black => 1,
+        };
+        for dx in [-1, 1] {
+            if let Some(square) = step(target, (dx, behind)) {
+                if let Some(kind) = piece_at(square) {
+                    consider(square, kind, &[PieceType::Pawn]);
+                }
+            }
+        }
+
+        for (steps, kinds) in [
+            (&KNIGHT_STEPS, [PieceType::Knight]),
+            (&KING_STEPS, [PieceType::King]),
+        ] {
+            for direction in steps {
+                if let Some(square) = step(target, *direction) {
+                    if let Some(kind) = piece_at(square) {
+                        consider(square, kind, &kinds);
+                    }
+                }
+            }
+        }
+
+        for (directions, kinds) in [
+            (&DIAGONALS, [PieceType::Bishop, PieceType::Queen]),
+            (&ORTHOGONALS, [PieceType::Rook, PieceType::Queen]),
+        ] {
+            for direction in directions {
+                let mut square = step(target, *direction);
+                while let Some(s) = square {
+                    if occupied & bit(s) != 0 {
+                        if let Some(kind) = piece_at(s) {
+                            consider(s, kind, &kinds);
+                        }
+                        break;
+                    }
+                    square = step(s, *direction);
+                }
+            }
+        }
+
+        best
+    }
+}
+
+fn bit((x, y): (usize, usize)) -> u64 {
+    1 << (y * 8 + x)
+}
+
+fn step((x, y): (usize, usize), (dx, dy): (i32, i32)) -> Option<(usize, usize)> {
+    let (x, y) = (x as i32 + dx, y as i32 + dy);
+
+    if (0..8).contains(&x) && (0..8).contains(&y) {
+        Some((x as usize, y as usize))
+    } else {
+        None
+    }
+}
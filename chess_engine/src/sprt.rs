@@ -0,0 +1,120 @@
+// Match statistics: the Elo difference between two players from their
+// results and a sequential probability ratio test of whether one is stronger
+
+// Two sided 95% interval of the normal distribution
+const Z95: f64 = 1.959964;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+// Tests whether the difference is at least `elo1` (accepted) or at most
+// `elo0` (rejected). `alpha` and `beta` are the chances of accepting and
+// rejecting wrongly
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Verdict {
+    Accepted,
+    Rejected,
+    Undecided,
+}
+
+impl MatchScore {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    // Points per game, 0 to 1
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    // Per game variance of the score
+    fn variance(&self) -> f64 {
+        let n = self.games() as f64;
+        let s = self.score();
+
+        (self.wins as f64 * (1.0 - s).powi(2)
+            + self.draws as f64 * (0.5 - s).powi(2)
+            + self.losses as f64 * s.powi(2))
+            / n
+    }
+
+    // The Elo difference and the margin of its 95% confidence interval.
+    // None before there are both points won and lost
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        if self.games() == 0 {
+            return None;
+        }
+
+        let s = self.score();
+        if s <= 0.0 || s >= 1.0 {
+            return None;
+        }
+
+        let deviation = (self.variance() / self.games() as f64).sqrt();
+        let low = elo((s - Z95 * deviation).max(f64::EPSILON));
+        let high = elo((s + Z95 * deviation).min(1.0 - f64::EPSILON));
+
+        Some((elo(s), (high - low) / 2.0))
+    }
+
+    // Log likelihood ratio of the difference being `elo1` against it being
+    // `elo0`, with the score approximated as normally distributed
+    pub fn llr(&self, elo0: f64, elo1: f64) -> f64 {
+        if self.games() == 0 {
+            return 0.0;
+        }
+
+        let variance = self.variance() / self.games() as f64;
+        if variance <= 0.0 {
+            return 0.0;
+        }
+
+        let (s0, s1) = (expected_score(elo0), expected_score(elo1));
+        (s1 - s0) * (2.0 * self.score() - s0 - s1) / (2.0 * variance)
+    }
+}
+
+impl Sprt {
+    // The LLR needed to reject and to accept
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    pub fn verdict(&self, score: &MatchScore) -> Verdict {
+        let llr = score.llr(self.elo0, self.elo1);
+        let (lower, upper) = self.bounds();
+
+        if llr >= upper {
+            Verdict::Accepted
+        } else if llr <= lower {
+            Verdict::Rejected
+        } else {
+            Verdict::Undecided
+        }
+    }
+}
+
+// Expected score against a player `elo` points weaker
+pub fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+// Elo difference from an expected score
+pub fn elo(score: f64) -> f64 {
+    400.0 * (score / (1.0 - score)).log10()
+}
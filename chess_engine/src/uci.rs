@@ -0,0 +1,519 @@
+use crate::game::Game;
+use crate::piece::*;
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError, TryRecvError};
+use std::thread::{self, spawn};
+use std::time::{Duration, Instant};
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+const QUIT_TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct UciMove {
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    pub promotion: Option<PieceType>,
+}
+
+// Scores are from the point of view of the side to move
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Score {
+    Centipawns(i32),
+    // Moves until mate, negative when the side to move gets mated
+    Mate(i32),
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Info {
+    pub depth: Option<u32>,
+    pub multipv: Option<u32>,
+    pub score: Option<Score>,
+    pub nodes: Option<u64>,
+    pub time: Option<u64>,
+    pub pv: Vec<UciMove>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum EngineEvent {
+    Info(Info),
+    // best is None when the engine had no legal move to play
+    BestMove {
+        best: Option<UciMove>,
+        ponder: Option<UciMove>,
+    },
+    Exited,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Limit {
+    Depth(u32),
+    // Milliseconds
+    MoveTime(u64),
+    Nodes(u64),
+    // Time left and increments in milliseconds
+    Clock {
+        wtime: u64,
+        btime: u64,
+        winc: u64,
+        binc: u64,
+    },
+    Infinite,
+}
+
+// An external engine process spoken to over UCI. Output is read on its own
+// thread, `read` hands over whatever has arrived since the last call
+pub struct Engine {
+    pub name: String,
+    pub author: String,
+    child: Child,
+    stdin: ChildStdin,
+    read_channel: mpsc::Receiver<String>,
+    searching: bool,
+    // Searching on the opponent's expected move, until ponderhit or stop
+    pondering: bool,
+    // Stopped searches whose bestmove hasn't arrived yet, their output is
+    // dropped so it can't be mistaken for the current search
+    stopped: usize,
+    exited: bool,
+}
+
+impl Engine {
+    // Starts `path` and waits for the engine to answer uciok and readyok
+    pub fn spawn(path: &str) -> io::Result<Engine> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (w_reader, r_reader) = mpsc::channel::<String>();
+
+        spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let sent = match line {
+                    Ok(line) => w_reader.send(line).is_ok(),
+                    Err(_) => false,
+                };
+
+                if !sent {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = Engine {
+            name: String::new(),
+            author: String::new(),
+            child,
+            stdin,
+            read_channel: r_reader,
+            searching: false,
+            pondering: false,
+            stopped: 0,
+            exited: false,
+        };
+
+        engine.send("uci")?;
+        engine.wait_for("uciok")?;
+        engine.wait_ready()?;
+
+        Ok(engine)
+    }
+
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> io::Result<()> {
+        match value {
+            Some(value) => self.send(&format!("setoption name {} value {}", name, value)),
+            None => self.send(&format!("setoption name {}", name)),
+        }
+    }
+
+    pub fn new_game(&mut self) -> io::Result<()> {
+        self.send("ucinewgame")?;
+        self.wait_ready()
+    }
+
+    // Blocks until the engine has caught up with everything sent to it
+    pub fn wait_ready(&mut self) -> io::Result<()> {
+        self.send("isready")?;
+        self.wait_for("readyok")
+    }
+
+    // Sets the position to the start position followed by `moves`
+    pub fn position(&mut self, moves: &[UciMove]) -> io::Result<()> {
+        self.send_position("startpos", moves)
+    }
+
+    pub fn position_fen(&mut self, fen: &str, moves: &[UciMove]) -> io::Result<()> {
+        self.send_position(&format!("fen {}", fen), moves)
+    }
+
+    fn send_position(&mut self, position: &str, moves: &[UciMove]) -> io::Result<()> {
+        let mut command = format!("position {}", position);
+        if !moves.is_empty() {
+            command.push_str(" moves");
+            for mv in moves {
+                command.push_str(&format!(" {}", mv));
+            }
+        }
+
+        self.send(&command)
+    }
+
+    pub fn go(&mut self, limit: Limit) -> io::Result<()> {
+        self.send(&format!("go {}", limit_arguments(limit)))?;
+        self.searching = true;
+        Ok(())
+    }
+
+    // Searches the position set with the opponent's expected move already
+    // played, while the opponent thinks. The engine only gives its bestmove
+    // after ponderhit, when `limit` starts to apply, or stop
+    pub fn go_ponder(&mut self, limit: Limit) -> io::Result<()> {
+        self.send(&format!("go ponder {}", limit_arguments(limit)))?;
+        self.searching = true;
+        self.pondering = true;
+        Ok(())
+    }
+
+    // The opponent played the expected move, the ponder search carries on
+    // as a normal one
+    pub fn ponderhit(&mut self) -> io::Result<()> {
+        if !self.pondering {
+            return Ok(());
+        }
+
+        self.send("ponderhit")?;
+        self.pondering = false;
+        Ok(())
+    }
+
+    // Stops the current search. Its bestmove and any info still on the way
+    // are thrown away
+    pub fn stop(&mut self) -> io::Result<()> {
+        if !self.searching {
+            return Ok(());
+        }
+
+        self.send("stop")?;
+        self.searching = false;
+        self.pondering = false;
+        self.stopped += 1;
+        Ok(())
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.searching
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.pondering
+    }
+
+    pub fn read(&mut self) -> VecDeque<EngineEvent> {
+        let mut queue = VecDeque::new();
+
+        loop {
+            match self.read_channel.try_recv() {
+                Ok(line) => queue.extend(self.handle_line(&line)),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    queue.extend(self.handle_exit());
+                    break;
+                }
+            }
+        }
+
+        queue
+    }
+
+    // Waits for the current search to end, giving its best move and the last
+    // info line with a score for the main line
+    pub fn finish_search(
+        &mut self,
+        timeout: Duration,
+    ) -> io::Result<(Option<UciMove>, Option<Info>)> {
+        let deadline = Instant::now() + timeout;
+        let mut last = None;
+
+        loop {
+            let left = deadline
+                .checked_duration_since(Instant::now())
+                .unwrap_or_default();
+
+            match self.next_event(left) {
+                Some(EngineEvent::Info(info)) => {
+                    if info.multipv.unwrap_or(1) == 1 && info.score.is_some() {
+                        last = Some(info);
+                    }
+                }
+                Some(EngineEvent::BestMove { best, .. }) => return Ok((best, last)),
+                Some(EngineEvent::Exited) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "engine exited",
+                    ))
+                }
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "engine didn't finish its search",
+                    ))
+                }
+            }
+        }
+    }
+
+    // Waits up to `timeout` for the next event
+    pub fn next_event(&mut self, timeout: Duration) -> Option<EngineEvent> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let left = deadline.checked_duration_since(Instant::now())?;
+
+            match self.read_channel.recv_timeout(left) {
+                Ok(line) => {
+                    if let Some(event) = self.handle_line(&line) {
+                        return Some(event);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => return None,
+                Err(RecvTimeoutError::Disconnected) => return self.handle_exit(),
+            }
+        }
+    }
+
+    fn handle_line(&mut self, line: &str) -> Option<EngineEvent> {
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next()? {
+            "bestmove" => {
+                if self.stopped > 0 {
+                    self.stopped -= 1;
+                    return None;
+                }
+
+                self.searching = false;
+                self.pondering = false;
+                Some(EngineEvent::BestMove {
+                    best: tokens.next().and_then(parse_move),
+                    ponder: match tokens.next() {
+                        Some("ponder") => tokens.next().and_then(parse_move),
+                        _ => None,
+                    },
+                })
+            }
+            "info" if self.stopped == 0 => parse_info(line).map(EngineEvent::Info),
+            _ => None,
+        }
+    }
+
+    fn handle_exit(&mut self) -> Option<EngineEvent> {
+        if self.exited {
+            return None;
+        }
+
+        self.exited = true;
+        self.searching = false;
+        self.pondering = false;
+        Some(EngineEvent::Exited)
+    }
+
+    // Reads lines until `expected`, keeping track of the engine's id
+    fn wait_for(&mut self, expected: &str) -> io::Result<()> {
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+
+        loop {
+            let left = deadline
+                .checked_duration_since(Instant::now())
+                .unwrap_or_default();
+
+            let line = match self.read_channel.recv_timeout(left) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("engine didn't answer {}", expected),
+                    ))
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "engine exited",
+                    ))
+                }
+            };
+
+            if line.trim() == expected {
+                return Ok(());
+            }
+
+            if let Some(id) = line.trim().strip_prefix("id ") {
+                if let Some(name) = id.strip_prefix("name ") {
+                    self.name = name.trim().to_owned();
+                } else if let Some(author) = id.strip_prefix("author ") {
+                    self.author = author.trim().to_owned();
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+
+        let deadline = Instant::now() + QUIT_TIMEOUT;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl fmt::Display for UciMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let square = |(x, y): (usize, usize)| format!("{}{}", (b'a' + x as u8) as char, y + 1);
+
+        write!(f, "{}{}", square(self.from), square(self.to))?;
+        match self.promotion {
+            Some(PieceType::Knight) => write!(f, "n"),
+            Some(PieceType::Bishop) => write!(f, "b"),
+            Some(PieceType::Rook) => write!(f, "r"),
+            Some(PieceType::Queen) => write!(f, "q"),
+            _ => Ok(()),
+        }
+    }
+}
+
+// The part of a go command after "go"
+fn limit_arguments(limit: Limit) -> String {
+    match limit {
+        Limit::Depth(depth) => format!("depth {}", depth),
+        Limit::MoveTime(time) => format!("movetime {}", time),
+        Limit::Nodes(nodes) => format!("nodes {}", nodes),
+        Limit::Clock {
+            wtime,
+            btime,
+            winc,
+            binc,
+        } => format!(
+            "wtime {} btime {} winc {} binc {}",
+            wtime, btime, winc, binc
+        ),
+        Limit::Infinite => "infinite".to_owned(),
+    }
+}
+
+// Parses long algebraic moves like "e2e4" or "e7e8q"
+pub fn parse_move(text: &str) -> Option<UciMove> {
+    let bytes = text.as_bytes();
+    if bytes.len() != 4 && bytes.len() != 5 {
+        return None;
+    }
+
+    let square = |file: u8, rank: u8| match (file, rank) {
+        (b'a'..=b'h', b'1'..=b'8') => Some(((file - b'a') as usize, (rank - b'1') as usize)),
+        _ => None,
+    };
+
+    let promotion = match bytes.get(4) {
+        None => None,
+        Some(b'n') => Some(PieceType::Knight),
+        Some(b'b') => Some(PieceType::Bishop),
+        Some(b'r') => Some(PieceType::Rook),
+        Some(b'q') => Some(PieceType::Queen),
+        Some(_) => return None,
+    };
+
+    Some(UciMove {
+        from: square(bytes[0], bytes[1])?,
+        to: square(bytes[2], bytes[3])?,
+        promotion,
+    })
+}
+
+// Plays `mv` if it's legal for the side to move. A pawn reaching the last
+// rank without a promotion piece becomes a queen
+pub fn play_move(game: &mut Game, mv: &UciMove) -> bool {
+    match game.board.board_squares[mv.from.0][mv.from.1].piece {
+        Some(piece) if piece.color == game.curr_player => {}
+        _ => return false,
+    }
+
+    if !game
+        .get_available_moves(mv.from)
+        .contains(&vec![mv.to.0, mv.to.1])
+    {
+        return false;
+    }
+
+    game.move_piece(mv.from, mv.to);
+    if game.promotable.is_some() {
+        game.promote(mv.promotion.unwrap_or(PieceType::Queen));
+    }
+
+    true
+}
+
+// Parses an info line, "info string" and lines without anything we use
+// give None
+pub fn parse_info(line: &str) -> Option<Info> {
+    let mut tokens = line.split_whitespace();
+    if tokens.next() != Some("info") {
+        return None;
+    }
+
+    let mut info = Info::default();
+    let mut any = false;
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "string" => return None,
+            "depth" => info.depth = tokens.next().and_then(|t| t.parse().ok()),
+            "multipv" => info.multipv = tokens.next().and_then(|t| t.parse().ok()),
+            "nodes" => info.nodes = tokens.next().and_then(|t| t.parse().ok()),
+            "time" => info.time = tokens.next().and_then(|t| t.parse().ok()),
+            "score" => {
+                let kind = tokens.next();
+                let value = tokens.next().and_then(|t| t.parse().ok());
+
+                info.score = match (kind, value) {
+                    (Some("cp"), Some(value)) => Some(Score::Centipawns(value)),
+                    (Some("mate"), Some(value)) => Some(Score::Mate(value)),
+                    _ => None,
+                };
+            }
+            "pv" => {
+                info.pv = tokens.by_ref().map_while(parse_move).collect();
+            }
+            "seldepth" | "nps" | "hashfull" | "tbhits" | "currmove" | "currmovenumber"
+            | "cpuload" => {
+                tokens.next();
+            }
+            _ => continue,
+        }
+        any = true;
+    }
+
+    if any {
+        Some(info)
+    } else {
+        None
+    }
+}
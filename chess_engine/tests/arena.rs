@@ -0,0 +1,128 @@
+use chess_engine::arena::*;
+use chess_engine::fen;
+use chess_engine::game::Outcome;
+use chess_engine::uci::{parse_move, Engine, UciMove};
+
+fn engine() -> Engine {
+    Engine::spawn(env!("CARGO_BIN_EXE_fake_engine")).unwrap()
+}
+
+fn moves(text: &str) -> Vec<UciMove> {
+    text.split_whitespace()
+        .map(|mv| parse_move(mv).unwrap())
+        .collect()
+}
+
+fn no_adjudication() -> Adjudication {
+    Adjudication {
+        resign_score: None,
+        draw_score: None,
+        max_moves: None,
+        ..Adjudication::default()
+    }
+}
+
+#[test]
+fn plays_until_an_engine_has_no_move() {
+    // The fake engine runs out of moves after 2... Nc6
+    let record = play_game(
+        &mut engine(),
+        &mut engine(),
+        &Opening::start(),
+        TimeControl::Depth(1),
+        &no_adjudication(),
+    )
+    .unwrap();
+
+    assert_eq!(record.sans, vec!["e4", "e5", "Nf3", "Nc6"]);
+    assert_eq!(record.moves, moves("e2e4 e7e5 g1f3 b8c6"));
+    assert_eq!(record.result, None);
+    assert_eq!(record.termination, "white gave no move");
+    assert_eq!(record.result_text(), "*");
+}
+
+#[test]
+fn plays_from_the_opening() {
+    let opening = Opening {
+        fen: fen::START.to_owned(),
+        moves: moves("e2e4 e7e5"),
+    };
+    let adjudication = Adjudication {
+        max_moves: Some(1),
+        ..no_adjudication()
+    };
+
+    let record = play_game(
+        &mut engine(),
+        &mut engine(),
+        &opening,
+        TimeControl::MoveTime(10),
+        &adjudication,
+    )
+    .unwrap();
+
+    assert_eq!(record.sans, vec!["e4", "e5", "Nf3", "Nc6"]);
+    assert_eq!(record.result, Some(Outcome::Draw));
+    assert_eq!(record.termination, "adjudication: move limit");
+
+    let pgn = record.to_pgn("Test", 3);
+    assert!(pgn.contains("[Round \"3\"]"));
+    assert!(pgn.contains("[Result \"1/2-1/2\"]"));
+    assert!(!pgn.contains("[FEN"));
+    assert!(pgn.contains("1. e4 e5 2. Nf3 Nc6 {adjudication: move limit} 1/2-1/2"));
+}
+
+#[test]
+fn adjudicates_draws() {
+    // The fake engine scores every position 10 centipawns for the side to
+    // move, so white sees +10 and -10
+    let adjudication = Adjudication {
+        draw_score: Some(10),
+        draw_moves: 1,
+        draw_after: 0,
+        ..no_adjudication()
+    };
+
+    let record = play_game(
+        &mut engine(),
+        &mut engine(),
+        &Opening::start(),
+        TimeControl::Clock {
+            base: 10000,
+            increment: 100,
+        },
+        &adjudication,
+    )
+    .unwrap();
+
+    assert_eq!(record.sans, vec!["e4", "e5"]);
+    assert_eq!(record.result, Some(Outcome::Draw));
+    assert_eq!(record.termination, "adjudication: draw");
+}
+
+#[test]
+fn writes_positions_with_black_to_move() {
+    let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+    let opening = Opening {
+        fen: fen.to_owned(),
+        moves: Vec::new(),
+    };
+
+    // The fake engine doesn't know the position and answers with 1. e4
+    let record = play_game(
+        &mut engine(),
+        &mut engine(),
+        &opening,
+        TimeControl::Depth(1),
+        &no_adjudication(),
+    )
+    .unwrap();
+
+    assert_eq!(record.result, None);
+    assert_eq!(record.termination, "black played unknown move e2e4");
+
+    let pgn = record.to_pgn("Test", 1);
+    assert!(pgn.contains("[SetUp \"1\"]"));
+    assert!(pgn.contains(&format!("[FEN \"{}\"]", fen)));
+    assert!(pgn.contains("1... {black played unknown move e2e4} *"));
+}
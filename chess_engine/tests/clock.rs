@@ -0,0 +1,270 @@
+use chess_engine::clock::*;
+use chess_engine::fen;
+use chess_engine::game::Outcome;
+use chess_engine::piece::Color::*;
+
+use std::time::Duration;
+
+fn secs(seconds: u64) -> Duration {
+    Duration::from_secs(seconds)
+}
+
+// A clock with white's clock running and the time source to drive it
+fn running(control: &str) -> (Clock, ManualTime) {
+    let time = ManualTime::new();
+    let mut clock = Clock::new(TimeControl::parse(control).unwrap(), time.clone());
+    clock.start(White);
+
+    (clock, time)
+}
+
+#[test]
+fn parses_time_controls() {
+    let control = TimeControl::parse("40/5400+30:1800+30").unwrap();
+    assert_eq!(
+        control.stages,
+        vec![
+            Stage {
+                moves: Some(40),
+                time: secs(5400),
+                timing: Timing::Fischer(secs(30)),
+            },
+            Stage {
+                moves: None,
+                time: secs(1800),
+                timing: Timing::Fischer(secs(30)),
+            },
+        ]
+    );
+
+    for text in [
+        "300",
+        "180+2",
+        "300+5d",
+        "300+5b",
+        "40/5400+30:1800+30",
+        "40/7200",
+        "60+0.5",
+    ] {
+        assert_eq!(TimeControl::parse(text).unwrap().to_string(), text);
+    }
+
+    assert_eq!(TimeControl::parse(""), None);
+    assert_eq!(TimeControl::parse("5+x"), None);
+    assert_eq!(TimeControl::parse("0/300"), None);
+    assert_eq!(TimeControl::parse("300:40/300"), None);
+}
+
+#[test]
+fn rejects_times_out_of_range() {
+    for text in [
+        "1e30",
+        "300+1e20",
+        "300+1e20d",
+        "-5",
+        "-0.5+2",
+        "inf",
+        "NaN",
+        "300+nan",
+    ] {
+        assert_eq!(TimeControl::parse(text), None, "{}", text);
+    }
+}
+
+#[test]
+fn rejects_huge_time_controls() {
+    // What a peer could put in a game setup to overflow the clock
+    for text in [
+        "1e19",
+        "300+1e19",
+        "300+1e19b",
+        "300+1e19d",
+        "40/300:1e19",
+        "40/1e19+5:300",
+    ] {
+        assert_eq!(TimeControl::parse(text), None, "{}", text);
+    }
+
+    let longest = MAX_TIME.as_secs();
+    assert_eq!(TimeControl::parse(&format!("{}", longest + 1)), None);
+
+    // The longest allowed still adds up without overflowing
+    let control = format!("1/{}+{}:{}+{}", longest, longest, longest, longest);
+    let (mut clock, time) = running(&control);
+    for _ in 0..100 {
+        time.advance(secs(1));
+        assert!(clock.press());
+    }
+    assert!(clock.remaining(White) > MAX_TIME);
+}
+
+#[test]
+fn runs_out_in_sudden_death() {
+    let (mut clock, time) = running("60");
+
+    time.advance(secs(20));
+    assert_eq!(clock.remaining(White), secs(40));
+    assert!(clock.press());
+    assert_eq!(clock.running(), Some(Black));
+
+    time.advance(secs(59));
+    assert_eq!(clock.remaining(Black), secs(1));
+    assert_eq!(clock.flagged(), None);
+
+    time.advance(secs(1));
+    assert_eq!(clock.flagged(), Some(Black));
+    assert!(!clock.press());
+    assert_eq!(clock.running(), None);
+    assert_eq!(clock.remaining(White), secs(40));
+}
+
+#[test]
+fn adds_increments_and_delays() {
+    let (mut clock, time) = running("60+5");
+    time.advance(secs(10));
+    clock.press();
+    assert_eq!(clock.remaining(White), secs(55));
+
+    // Bronstein gives back the time used, up to the bonus
+    let (mut clock, time) = running("60+5b");
+    time.advance(secs(3));
+    clock.press();
+    assert_eq!(clock.remaining(White), secs(60));
+    time.advance(secs(10));
+    clock.press();
+    assert_eq!(clock.remaining(Black), secs(55));
+
+    // With a delay the clock waits before running down
+    let (mut clock, time) = running("60+5d");
+    time.advance(secs(3));
+    assert_eq!(clock.remaining(White), secs(60));
+    assert_eq!(clock.delay_left(White), secs(2));
+    time.advance(secs(7));
+    assert_eq!(clock.remaining(White), secs(55));
+    assert_eq!(clock.delay_left(White), Duration::ZERO);
+    clock.press();
+    assert_eq!(clock.remaining(White), secs(55));
+}
+
+fn play(clock: &mut Clock, time: &ManualTime, seconds: u64) {
+    time.advance(secs(seconds));
+    assert!(clock.press());
+}
+
+#[test]
+fn moves_through_stages() {
+    let (mut clock, time) = running("2/100:3/50:10");
+
+    // Two moves each, then the second stage's time is added
+    play(&mut clock, &time, 10);
+    play(&mut clock, &time, 20);
+    play(&mut clock, &time, 10);
+    assert_eq!(clock.remaining(White), secs(130));
+    play(&mut clock, &time, 20);
+    assert_eq!(clock.remaining(Black), secs(110));
+
+    for _ in 0..6 {
+        play(&mut clock, &time, 1);
+    }
+    assert_eq!(clock.moves(White), 5);
+    assert_eq!(clock.remaining(White), secs(137));
+
+    // A repeating last stage adds its time again
+    let (mut clock, time) = running("1/10");
+    time.advance(secs(4));
+    clock.press();
+    assert_eq!(clock.remaining(White), secs(16));
+}
+
+#[test]
+fn pauses() {
+    let (mut clock, time) = running("60");
+    time.advance(secs(10));
+    clock.pause();
+    time.advance(secs(100));
+
+    assert_eq!(clock.running(), None);
+    assert_eq!(clock.remaining(White), secs(50));
+    assert!(!clock.press());
+
+    clock.start(White);
+    time.advance(secs(10));
+    assert_eq!(clock.remaining(White), secs(40));
+}
+
+#[test]
+fn decides_games_on_time() {
+    let (clock, time) = running("10");
+    let game = fen::parse(fen::START).unwrap();
+
+    assert_eq!(clock.outcome(&game), None);
+    time.advance(secs(10));
+    assert_eq!(clock.outcome(&game), Some(Outcome::BlackWins));
+
+    // A knight can mate with help from white's own pieces
+    let game = fen::parse("4k3/8/8/3n4/8/8/8/4K2R w - - 0 1").unwrap();
+    assert_eq!(clock.outcome(&game), Some(Outcome::BlackWins));
+
+    // But not against a bare king
+    let game = fen::parse("4k3/8/8/3n4/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(clock.outcome(&game), Some(Outcome::Draw));
+
+    let game = fen::parse("4k3/8/8/8/8/8/8/4K2R w - - 0 1").unwrap();
+    assert_eq!(clock.outcome(&game), Some(Outcome::Draw));
+}
+
+#[test]
+fn takes_reported_times_within_the_lag() {
+    // The move took a second to arrive, the mover says it used 9 seconds
+    let (mut clock, time) = running("60+1");
+    time.advance(secs(10));
+    assert!(clock.press_remote(secs(52), secs(2)));
+    assert_eq!(clock.remaining(White), secs(52));
+    assert_eq!(clock.running(), Some(Black));
+
+    // Claiming to have used less than the lag allows is cut down to it
+    time.advance(secs(10));
+    assert!(clock.press_remote(secs(60), secs(2)));
+    assert_eq!(clock.remaining(Black), secs(53));
+
+    // Run out here, but not on the mover's clock
+    time.advance(secs(53));
+    assert_eq!(clock.flagged(), Some(White));
+    assert!(clock.press_remote(secs(1), secs(2)));
+    assert_eq!(clock.remaining(White), secs(1));
+    assert_eq!(clock.flagged(), None);
+
+    time.advance(secs(60));
+    assert!(!clock.press_remote(Duration::ZERO, secs(2)));
+    assert_eq!(clock.flagged(), Some(Black));
+}
+
+#[test]
+fn corrects_times() {
+    let (mut clock, time) = running("60");
+    time.advance(secs(60));
+    assert_eq!(clock.flagged(), Some(White));
+
+    clock.correct(White, secs(1), secs(2));
+    assert_eq!(clock.flagged(), None);
+    assert_eq!(clock.remaining(White), secs(1));
+    assert_eq!(clock.running(), Some(White));
+
+    clock.correct(Black, secs(100), secs(2));
+    assert_eq!(clock.remaining(Black), secs(62));
+    clock.correct(Black, secs(30), secs(2));
+    assert_eq!(clock.remaining(Black), secs(30));
+}
+
+#[test]
+fn sets_times() {
+    let (mut clock, time) = running("60");
+    time.advance(secs(10));
+    clock.set_remaining(White, secs(100));
+    clock.set_remaining(Black, secs(5));
+    assert_eq!(clock.remaining(White), secs(100));
+    assert_eq!(clock.remaining(Black), secs(5));
+
+    time.advance(secs(10));
+    assert_eq!(clock.remaining(White), secs(90));
+}
@@ -0,0 +1,116 @@
+use chess_engine::endgame::*;
+use chess_engine::game::Game;
+use chess_engine::piece::{Color, Piece, PieceType};
+
+use Color::*;
+use DistanceToMate::*;
+use PieceType::*;
+
+fn position(pieces: &[(&str, PieceType, Color)], to_move: Color) -> Game {
+    let mut game = Game::new();
+    for column in game.board.board_squares.iter_mut() {
+        for square in column.iter_mut() {
+            square.piece = None;
+        }
+    }
+
+    for (name, kind, color) in pieces {
+        let bytes = name.as_bytes();
+        let (x, y) = ((bytes[0] - b'a') as usize, (bytes[1] - b'1') as usize);
+        game.board.board_squares[x][y].piece = Some(Piece::new(*kind, *color));
+    }
+
+    game.curr_player = to_move;
+    game
+}
+
+#[test]
+fn longest_mates_match_known_values() {
+    assert_eq!(longest_mate(Queen), Some(10));
+    assert_eq!(longest_mate(Rook), Some(16));
+    assert_eq!(longest_mate(Pawn), Some(28));
+    assert_eq!(longest_mate(Knight), None);
+}
+
+#[test]
+fn probes_mates_and_stalemates() {
+    let mated = [
+        ("a8", King, Black),
+        ("b6", King, White),
+        ("h8", Rook, White),
+    ];
+    assert_eq!(probe(&position(&mated, Black)), Some(Loss(0)));
+
+    let stalemate = [
+        ("a8", King, Black),
+        ("e1", King, White),
+        ("b6", Queen, White),
+    ];
+    assert_eq!(probe(&position(&stalemate, Black)), Some(Draw));
+
+    let mate_in_one = [
+        ("a8", King, Black),
+        ("b6", King, White),
+        ("h7", Queen, White),
+    ];
+    assert_eq!(probe(&position(&mate_in_one, White)), Some(Win(1)));
+
+    // Same mate with colours swapped and the board flipped
+    let mated = [
+        ("a1", King, White),
+        ("b3", King, Black),
+        ("h1", Rook, Black),
+    ];
+    assert_eq!(probe(&position(&mated, White)), Some(Loss(0)));
+}
+
+#[test]
+fn probes_pawn_endings() {
+    // Rook pawn with the defending king in the corner can't be won
+    let corner = [
+        ("h8", King, Black),
+        ("g6", King, White),
+        ("h5", Pawn, White),
+    ];
+    assert_eq!(probe(&position(&corner, White)), Some(Draw));
+    assert_eq!(probe(&position(&corner, Black)), Some(Draw));
+
+    // King on the sixth rank in front of its pawn wins whoever moves
+    let sixth = [
+        ("e8", King, Black),
+        ("e6", King, White),
+        ("e5", Pawn, White),
+    ];
+    assert!(matches!(probe(&position(&sixth, White)), Some(Win(_))));
+    assert!(matches!(probe(&position(&sixth, Black)), Some(Loss(_))));
+
+    let flipped = [
+        ("e1", King, White),
+        ("e3", King, Black),
+        ("e4", Pawn, Black),
+    ];
+    assert_eq!(
+        probe(&position(&flipped, Black)),
+        probe(&position(&sixth, White))
+    );
+}
+
+#[test]
+fn ignores_other_material() {
+    assert_eq!(probe(&Game::new()), None);
+
+    let two_rooks = [
+        ("a8", King, Black),
+        ("e1", King, White),
+        ("h7", Rook, White),
+        ("g6", Rook, White),
+    ];
+    assert_eq!(probe(&position(&two_rooks, White)), None);
+
+    let kings_touching = [
+        ("a8", King, Black),
+        ("b7", King, White),
+        ("h1", Rook, White),
+    ];
+    assert_eq!(probe(&position(&kings_touching, White)), None);
+}
@@ -0,0 +1,114 @@
+use chess_engine::epd::*;
+use chess_engine::fen;
+use chess_engine::piece::{Color, PieceType};
+use chess_engine::uci::{parse_move, Engine, Limit};
+
+use std::time::Duration;
+
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -";
+
+#[test]
+fn parses_operations() {
+    let record =
+        parse_line("1k1r4/pp1b1R2/3q2pp/4p3/2B5/4Q3/PPP2B2/2K5 b - - bm Qd1+; id \"WAC.001\";")
+            .unwrap();
+    assert_eq!(
+        record.fen,
+        "1k1r4/pp1b1R2/3q2pp/4p3/2B5/4Q3/PPP2B2/2K5 b - - 0 1"
+    );
+    assert_eq!(record.id.as_deref(), Some("WAC.001"));
+    assert_eq!(record.bm, vec!["Qd1+"]);
+
+    let record = parse_line(&format!(
+        "{} bm e4 d4; am f3; id \"start 1\"; c0 \"Nunn; 1991\"; hmvc 3; fmvn 12",
+        START
+    ))
+    .unwrap();
+    assert_eq!(record.fen, format!("{} 3 12", START));
+    assert_eq!(record.bm, vec!["e4", "d4"]);
+    assert_eq!(record.am, vec!["f3"]);
+    assert_eq!(record.id.as_deref(), Some("start 1"));
+    assert_eq!(record.c0.as_deref(), Some("Nunn; 1991"));
+
+    assert_eq!(parse_line(""), None);
+    assert_eq!(parse_line("# a comment"), None);
+    assert_eq!(parse_line("8/8/8 w - - bm e4;"), None);
+}
+
+#[test]
+fn loads_fen() {
+    let game = fen::parse(fen::START).unwrap();
+    assert_eq!(game.curr_player, Color::White);
+    for x in 0..8 {
+        for y in 0..8 {
+            let expected = chess_engine::game::Game::new().board.board_squares[x][y].piece;
+            assert_eq!(game.board.board_squares[x][y].piece, expected);
+        }
+    }
+
+    let game = fen::parse("4k3/8/8/8/8/8/8/4K2R b K - 5 40").unwrap();
+    assert_eq!(game.curr_player, Color::Black);
+    let rook = game.board.board_squares[7][0].piece.unwrap();
+    assert_eq!(
+        (rook.piece_type, rook.color),
+        (PieceType::Rook, Color::White)
+    );
+
+    assert!(fen::parse("8/8/8/8/8/8/8 w - -").is_none());
+    assert!(fen::parse("9/8/8/8/8/8/8/8 w - -").is_none());
+    assert!(fen::parse("8/8/8/8/8/8/8/8 x - -").is_none());
+    assert!(fen::parse("8/8/8/8/8/8/8/8 w - e4").is_none());
+}
+
+#[test]
+fn loads_pawns_beside_enemy_pawns() {
+    // Full castling rights and no en passant square leave no move in the
+    // history for the pawns to check
+    for position in [
+        "4k3/8/8/4Pp2/8/8/8/4K3 w KQkq - 0 1",
+        "4k3/8/8/3pP3/8/8/8/4K3 w KQkq - 0 1",
+        "4k3/8/8/8/3Pp3/8/8/4K3 b KQkq - 0 1",
+    ] {
+        let mut game = fen::parse(position).unwrap();
+        for x in 0..8 {
+            for y in 0..8 {
+                game.get_available_moves((x, y));
+            }
+        }
+    }
+
+    let record =
+        parse_line("r1bqkbnr/ppp1p1pp/2n5/3pPp2/8/5N2/PPPP1PPP/RNBQKB1R w KQkq - bm d4;").unwrap();
+    assert!(record.solved_by(&parse_move("d2d4").unwrap()));
+    assert!(!record.solved_by(&parse_move("e5f6").unwrap()));
+}
+
+#[test]
+fn judges_moves() {
+    let record = parse_line(&format!("{} bm e4 Nf3; am f3;", START)).unwrap();
+    assert!(record.solved_by(&parse_move("e2e4").unwrap()));
+    assert!(record.solved_by(&parse_move("g1f3").unwrap()));
+    assert!(!record.solved_by(&parse_move("d2d4").unwrap()));
+
+    let avoid = parse_line(&format!("{} am f3;", START)).unwrap();
+    assert!(avoid.solved_by(&parse_move("d2d4").unwrap()));
+    assert!(!avoid.solved_by(&parse_move("f2f3").unwrap()));
+}
+
+#[test]
+fn solves_with_an_engine() {
+    let mut engine = Engine::spawn(env!("CARGO_BIN_EXE_fake_engine")).unwrap();
+    let timeout = Duration::from_secs(5);
+
+    let record = parse_line(&format!("{} bm e4; id \"e4\";", START)).unwrap();
+    let result = record.solve(&mut engine, Limit::Depth(2), timeout).unwrap();
+    assert_eq!(result.best, parse_move("e2e4"));
+    assert_eq!(result.san.as_deref(), Some("e4"));
+    assert!(result.solved);
+
+    let record = parse_line(&format!("{} am e4;", START)).unwrap();
+    let result = record
+        .solve(&mut engine, Limit::MoveTime(10), timeout)
+        .unwrap();
+    assert!(!result.solved);
+}
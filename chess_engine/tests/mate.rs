@@ -0,0 +1,131 @@
+use chess_engine::fen;
+use chess_engine::game::Game;
+use chess_engine::mate::*;
+use chess_engine::uci::{self, parse_move};
+
+// Plays the tree out and checks that every line ends in mate in time
+fn check_line(game: &Game, line: &MateLine, moves: usize) {
+    assert!(moves >= 1);
+
+    let mut next = game.clone();
+    assert!(uci::play_move(&mut next, &line.mv));
+
+    if line.defences.is_empty() {
+        let (checkmate, _) = next.clone().check_for_win();
+        assert!(checkmate, "{} doesn't mate", line.san);
+        return;
+    }
+
+    for defence in &line.defences {
+        let mut position = next.clone();
+        assert!(uci::play_move(&mut position, &defence.mv));
+        assert!(!defence.replies.is_empty(), "{} refutes", defence.san);
+
+        for reply in &defence.replies {
+            check_line(&position, reply, moves - 1);
+        }
+    }
+}
+
+#[test]
+fn finds_mate_in_one() {
+    let game = fen::parse("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    let solution = solve(&game, 1);
+
+    assert!(solution.is_solved());
+    assert_eq!(solution.shortest, Some(1));
+    assert_eq!(solution.keys.len(), 1);
+    assert_eq!(solution.keys[0].san, "Ra8#");
+    assert!(solution.keys[0].defences.is_empty());
+    assert!(solution.cooks(&parse_move("a1a8").unwrap()).is_empty());
+}
+
+#[test]
+fn finds_fools_mate() {
+    // Neither h2-h4 nor g2-g3 can block the check
+    let mut game = Game::new();
+    for mv in ["f2f3", "e7e5", "g2g4"] {
+        assert!(uci::play_move(&mut game, &parse_move(mv).unwrap()));
+    }
+
+    let solution = solve(&game, 1);
+    let keys: Vec<&str> = solution.keys.iter().map(|key| key.san.as_str()).collect();
+    assert_eq!(keys, vec!["Qh4#"]);
+}
+
+#[test]
+fn finds_castling_keys() {
+    // Castling short mates and castling long doesn't, with both allowed
+    for (position, mut expected) in [
+        (
+            "4rkr1/4p1p1/8/8/8/8/8/R3K2R w KQ - 0 1",
+            vec!["O-O#", "Rf1#"],
+        ),
+        (
+            "r3k2r/8/8/8/8/8/2P1P3/2RKR3 b kq - 0 1",
+            vec!["O-O-O#", "Rd8#"],
+        ),
+    ] {
+        let game = fen::parse(position).unwrap();
+        let solution = solve(&game, 1);
+
+        let mut keys: Vec<&str> = solution.keys.iter().map(|key| key.san.as_str()).collect();
+        keys.sort();
+        expected.sort();
+        assert_eq!(keys, expected, "{}", position);
+
+        for key in &solution.keys {
+            check_line(&game, key, 1);
+        }
+    }
+}
+
+#[test]
+fn disproves_mates() {
+    // 1. Rh8+ Ka7 and 1. Ra1+ Kb8 both get away
+    let game = fen::parse("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap();
+    let solution = solve(&game, 1);
+
+    assert!(!solution.is_solved());
+    assert_eq!(solution.shortest, None);
+    assert_eq!(solution.to_string(), "no mate in 1\n");
+
+    // Black to move has nothing at all
+    let game = fen::parse("k7/8/2K5/8/8/8/8/7R b - - 0 1").unwrap();
+    assert!(!solve(&game, 2).is_solved());
+}
+
+#[test]
+fn counts_stalemate_as_a_defence() {
+    // 1. Qb6 would mate next move if it didn't stalemate
+    let game = fen::parse("k7/2K5/8/8/8/8/8/1Q6 w - - 0 1").unwrap();
+    let solution = solve(&game, 2);
+    let stalemating = parse_move("b1b6").unwrap();
+
+    assert!(solution.is_solved());
+    assert!(solution.keys.iter().all(|key| key.mv != stalemating));
+}
+
+#[test]
+fn solves_mate_in_two_with_cooks() {
+    let game = fen::parse("k7/8/2K5/8/8/8/8/7R w - - 0 1").unwrap();
+    let solution = solve(&game, 2);
+    let intended = parse_move("c6b6").unwrap();
+
+    assert!(solution.is_solved());
+    assert_eq!(solution.shortest, Some(2));
+    let keys: Vec<&str> = solution.keys.iter().map(|key| key.san.as_str()).collect();
+    assert_eq!(keys, vec!["Kb6", "Kc7"]);
+
+    let cooks = solution.cooks(&intended);
+    assert_eq!(cooks.len(), 1);
+    assert_eq!(cooks[0].san, "Kc7");
+
+    for key in &solution.keys {
+        check_line(&game, key, 2);
+    }
+
+    // 1. Kb6 Kb8 2. Rh8#
+    let text = solution.to_string();
+    assert!(text.contains("1. Kb6\n    1... Kb8\n        2. Rh8#\n"));
+}
@@ -0,0 +1,147 @@
+use chess_engine::fen;
+use chess_engine::piece::Color::*;
+use chess_engine::piece::PieceType::*;
+use chess_engine::uci;
+
+fn square(name: &str) -> (usize, usize) {
+    let bytes = name.as_bytes();
+    ((bytes[0] - b'a') as usize, (bytes[1] - b'1') as usize)
+}
+
+fn name(square: &[usize]) -> String {
+    format!("{}{}", (b'a' + square[0] as u8) as char, square[1] + 1)
+}
+
+// The squares the piece on `from` can move to, sorted
+fn moves(position: &str, from: &str) -> Vec<String> {
+    let mut game = fen::parse(position).unwrap();
+
+    let mut moves: Vec<String> = game
+        .get_available_moves(square(from))
+        .iter()
+        .map(|to| name(to))
+        .collect();
+    moves.sort();
+    moves
+}
+
+#[test]
+fn pawns_attack_diagonally() {
+    let mut game = fen::parse("4k3/8/8/3p4/8/8/8/4K3 w - - 0 1").unwrap();
+    let board = &mut game.board;
+
+    // Empty squares count, the square in front doesn't
+    assert!(board.is_square_attacked(square("c4"), White));
+    assert!(board.is_square_attacked(square("e4"), White));
+    assert!(!board.is_square_attacked(square("d4"), White));
+    assert!(!board.is_square_attacked(square("d6"), White));
+    assert!(!board.is_square_attacked(square("c6"), Black));
+
+    // A king can walk in front of a pawn but not beside it
+    assert_eq!(
+        moves("4k3/8/8/8/8/8/3p4/4K3 w - - 0 1", "e1"),
+        ["d1", "d2", "e2", "f1", "f2"]
+    );
+    assert_eq!(
+        moves("4k3/8/8/8/8/3p4/8/4K3 w - - 0 1", "e1"),
+        ["d1", "d2", "f1", "f2"]
+    );
+}
+
+#[test]
+fn kings_cant_retreat_along_the_check() {
+    // e1 is behind the king, out of sight of the rook only while the king
+    // stands on e2
+    assert_eq!(
+        moves("4k3/8/8/8/4r3/8/4K3/8 w - - 0 1", "e2"),
+        ["d1", "d2", "d3", "f1", "f2", "f3"]
+    );
+    assert_eq!(
+        moves("4k3/8/8/8/1b6/8/3K4/8 w - - 0 1", "d2"),
+        ["c1", "c2", "d1", "d3", "e2", "e3"]
+    );
+}
+
+#[test]
+fn pinned_pieces_stay_on_the_pin() {
+    // The rook on a1 is found first, the one on e2 is the pinned one
+    assert_eq!(
+        moves("k3r3/8/8/8/8/8/4R3/R3K3 w - - 0 1", "e2"),
+        ["e3", "e4", "e5", "e6", "e7", "e8"]
+    );
+    assert_eq!(moves("k3r3/8/8/8/8/8/4R3/R3K3 w - - 0 1", "a1").len(), 10);
+
+    assert!(moves("k7/8/8/8/1b6/8/3N4/1N2K3 w - - 0 1", "d2").is_empty());
+    assert_eq!(
+        moves("k7/8/8/8/1b6/8/3N4/1N2K3 w - - 0 1", "b1"),
+        ["a3", "c3"]
+    );
+}
+
+#[test]
+fn double_steps_dont_jump() {
+    assert!(moves("4k3/8/8/8/8/4n3/4P3/4K3 w - - 0 1", "e2").is_empty());
+    assert_eq!(moves("4k3/8/8/8/4n3/8/4P3/4K3 w - - 0 1", "e2"), ["e3"]);
+
+    assert!(moves("4k3/4p3/4N3/8/8/8/8/4K3 b - - 0 1", "e7").is_empty());
+    assert_eq!(moves("4k3/4p3/8/4N3/8/8/8/4K3 b - - 0 1", "e7"), ["e6"]);
+
+    assert_eq!(moves("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", "e2"), ["e3", "e4"]);
+}
+
+#[test]
+fn castles_both_ways() {
+    let both = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+    assert_eq!(
+        moves(both, "e1"),
+        ["c1", "d1", "d2", "e2", "f1", "f2", "g1"]
+    );
+    assert_eq!(
+        moves(&both.replace(" w ", " b "), "e8"),
+        ["c8", "d7", "d8", "e7", "f7", "f8", "g8"]
+    );
+
+    // Only the rights the FEN gives
+    assert_eq!(
+        moves("r3k2r/8/8/8/8/8/8/R3K2R w K - 0 1", "e1"),
+        ["d1", "d2", "e2", "f1", "f2", "g1"]
+    );
+    assert_eq!(
+        moves("r3k2r/8/8/8/8/8/8/R3K2R w Q - 0 1", "e1"),
+        ["c1", "d1", "d2", "e2", "f1", "f2"]
+    );
+
+    // The king can't cross an attacked square but the rook can, and it needs
+    // a rook to castle with
+    assert_eq!(
+        moves("1r2k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", "e1"),
+        ["c1", "d1", "d2", "e2", "f1", "f2", "g1"]
+    );
+    assert_eq!(
+        moves("3rk3/8/8/8/8/8/8/R3K2R w KQ - 0 1", "e1"),
+        ["e2", "f1", "f2", "g1"]
+    );
+    assert_eq!(
+        moves("4k3/8/8/8/8/8/8/4K2R w KQ - 0 1", "e1"),
+        ["d1", "d2", "e2", "f1", "f2", "g1"]
+    );
+}
+
+#[test]
+fn rooks_dont_castle() {
+    let both = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+    assert_eq!(
+        moves(both, "h1"),
+        ["f1", "g1", "h2", "h3", "h4", "h5", "h6", "h7", "h8"]
+    );
+
+    // Moving a rook next to the king only moves the rook
+    for (mv, rook) in [("h1f1", (5, 0)), ("a1d1", (3, 0))] {
+        let mut game = fen::parse(both).unwrap();
+        assert!(uci::play_move(&mut game, &uci::parse_move(mv).unwrap()));
+
+        let squares = &game.board.board_squares;
+        assert_eq!(squares[rook.0][rook.1].piece.unwrap().piece_type, Rook);
+        assert_eq!(squares[4][0].piece.unwrap().piece_type, King);
+    }
+}
@@ -0,0 +1,117 @@
+use chess_engine::game::Game;
+use chess_engine::pgn::*;
+use chess_engine::piece::{Color, PieceType};
+
+const GAMES: &str = r#"[Event "Club \"open\""]
+[White "Alice"]
+[Black "Bob"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 {main line} Nc6 (2... d6 3. d4 (3. Bc4)) 3. Bb5 $1 a6?!
+; a rest of line comment 4. d4
+4.Ba4 Nf6 5.O-O 1-0
+
+[White "Bob"]
+[Black "Alice"]
+
+1. d4 d5 *
+"#;
+
+#[test]
+fn splits_games_and_skips_annotations() {
+    let games = parse(GAMES);
+    assert_eq!(games.len(), 2);
+
+    assert_eq!(games[0].tag("Event"), Some("Club \"open\""));
+    assert_eq!(games[0].tag("White"), Some("Alice"));
+    assert_eq!(games[0].result.as_deref(), Some("1-0"));
+    assert_eq!(
+        games[0].moves,
+        vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O"]
+    );
+
+    assert_eq!(games[1].moves, vec!["d4", "d5"]);
+    assert_eq!(games[1].result.as_deref(), Some("*"));
+}
+
+#[test]
+fn plays_mainline_with_castling() {
+    let mut game = Game::new();
+    for san in &parse(GAMES)[0].moves {
+        assert!(play_san(&mut game, san).is_some(), "couldn't play {}", san);
+    }
+
+    let king = game.board.board_squares[6][0].piece.unwrap();
+    let rook = game.board.board_squares[5][0].piece.unwrap();
+    assert_eq!(
+        (king.piece_type, rook.piece_type),
+        (PieceType::King, PieceType::Rook)
+    );
+    assert_eq!(game.curr_player, Color::Black);
+}
+
+#[test]
+fn resolves_disambiguation_and_promotion() {
+    let mut game = Game::new();
+    for san in &["d4", "d5", "Nf3", "Nf6"] {
+        play_san(&mut game, san).unwrap();
+    }
+    assert!(parse_san(&game, "Nd2").is_none());
+    assert_eq!(parse_san(&game, "Nbd2").unwrap().from, (1, 0));
+    assert_eq!(parse_san(&game, "N3d2").unwrap().from, (5, 2));
+
+    let mut game = Game::new();
+    for san in &["h4", "g5", "hxg5", "h6", "gxh6", "Bg7", "h7", "e6"] {
+        play_san(&mut game, san).unwrap();
+    }
+
+    let mv = play_san(&mut game, "hxg8=N+").unwrap();
+    assert_eq!((mv.from, mv.to), ((7, 6), (6, 7)));
+    assert_eq!(mv.promotion, Some(PieceType::Knight));
+    assert_eq!(
+        game.board.board_squares[6][7].piece.unwrap().piece_type,
+        PieceType::Knight
+    );
+}
+
+#[test]
+fn writes_san() {
+    let mut game = Game::new();
+    for san in &["d4", "d5", "Nf3", "Nf6", "Bf4", "e6", "e3", "Bd6"] {
+        play_san(&mut game, san).unwrap();
+    }
+
+    // Files are preferred over ranks to tell pieces apart
+    for (input, san) in &[("Nbd2", "Nbd2"), ("N3d2", "Nfd2"), ("Bxd6", "Bxd6")] {
+        let mv = parse_san(&game, input).unwrap();
+        assert_eq!(to_san(&game, &mv).as_deref(), Some(*san));
+    }
+
+    play_san(&mut game, "Bd3").unwrap();
+    play_san(&mut game, "Nc6").unwrap();
+    let mv = parse_san(&game, "O-O").unwrap();
+    assert_eq!(to_san(&game, &mv).as_deref(), Some("O-O"));
+
+    let illegal = PgnMove {
+        from: (3, 3),
+        to: (3, 4),
+        promotion: None,
+    };
+    assert_eq!(to_san(&game, &illegal), None);
+
+    let mut game = Game::new();
+    for san in &["h4", "g5", "hxg5", "h6", "gxh6", "Bg7", "h7", "e6"] {
+        play_san(&mut game, san).unwrap();
+    }
+    let mv = parse_san(&game, "hxg8=N").unwrap();
+    assert_eq!(to_san(&game, &mv).as_deref(), Some("hxg8=N"));
+
+    let mut game = Game::new();
+    for san in &["e4", "e5", "Bc4", "Nc6", "Qh5", "Nf6"] {
+        play_san(&mut game, san).unwrap();
+    }
+    let mv = parse_san(&game, "Qxf7").unwrap();
+    assert_eq!(to_san(&game, &mv).as_deref(), Some("Qxf7#"));
+    let mv = parse_san(&game, "Bxf7").unwrap();
+    assert_eq!(to_san(&game, &mv).as_deref(), Some("Bxf7+"));
+}
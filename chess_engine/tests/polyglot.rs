@@ -0,0 +1,146 @@
+use chess_engine::game::Game;
+use chess_engine::piece::PieceType;
+use chess_engine::polyglot::*;
+
+fn square(name: &str) -> (usize, usize) {
+    let bytes = name.as_bytes();
+    ((bytes[0] - b'a') as usize, (bytes[1] - b'1') as usize)
+}
+
+fn play(moves: &[&str]) -> Game {
+    let mut game = Game::new();
+    for mv in moves {
+        game.move_piece(square(&mv[0..2]), square(&mv[2..4]));
+    }
+    game
+}
+
+fn entry(game: &Game, mv: &str, weight: u16) -> BookEntry {
+    BookEntry {
+        key: key(game),
+        mv: encode_game_move(game, square(&mv[0..2]), square(&mv[2..4]), None),
+        weight,
+        learn: 0,
+    }
+}
+
+fn book(entries: &[BookEntry]) -> Book {
+    let bytes: Vec<u8> = entries
+        .iter()
+        .flat_map(|e| encode_entry(e).to_vec())
+        .collect();
+    Book::from_bytes(&bytes).unwrap()
+}
+
+#[test]
+fn keys_match_reference_positions() {
+    let cases: [(&[&str], u64); 7] = [
+        (&[], 0x463b96181691fc9c),
+        (&["e2e4"], 0x823c9b50fd114196),
+        (&["e2e4", "d7d5"], 0x0756b94461c50fb0),
+        (&["e2e4", "d7d5", "e4e5"], 0x662fafb965db29d4),
+        (&["e2e4", "d7d5", "e4e5", "f7f5"], 0x22a48b5a8e47ff78),
+        (
+            &["e2e4", "d7d5", "e4e5", "f7f5", "e1e2"],
+            0x652a607ca3f242c1,
+        ),
+        (
+            &["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"],
+            0x00fdd303c946bdd9,
+        ),
+    ];
+
+    for (moves, expected) in cases.iter() {
+        assert_eq!(key(&play(moves)), *expected, "after {:?}", moves);
+    }
+}
+
+#[test]
+fn selects_best_and_weighted_moves() {
+    let game = Game::new();
+    let book = book(&[entry(&game, "d2d4", 5), entry(&game, "e2e4", 10)]);
+
+    let best = book.best_move(&game).unwrap();
+    assert_eq!((best.from, best.to), (square("e2"), square("e4")));
+
+    let picks: Vec<_> = (0..15)
+        .map(|roll| book.weighted_move(&game, roll).unwrap().to)
+        .collect();
+    assert_eq!(picks.iter().filter(|to| **to == square("d4")).count(), 5);
+    assert_eq!(picks.iter().filter(|to| **to == square("e4")).count(), 10);
+
+    assert!(book.best_move(&play(&["e2e4"])).is_none());
+}
+
+#[test]
+fn skips_illegal_and_translates_castling() {
+    let game = play(&["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "f8c5"]);
+    let book = book(&[entry(&game, "e1g1", 3), entry(&game, "a1a5", 50)]);
+
+    assert_eq!(
+        book.entries(key(&game))[0].mv,
+        encode_move(square("e1"), square("h1"), None)
+    );
+
+    let moves = book.moves(&game);
+    assert_eq!(moves.len(), 1);
+    assert_eq!((moves[0].from, moves[0].to), (square("e1"), square("g1")));
+    assert_eq!(moves[0].promotion, None::<PieceType>);
+}
+
+#[test]
+fn opens_book_files() {
+    let game = Game::new();
+    let path = std::env::temp_dir().join("hishamah-chess-polyglot-test.bin");
+    std::fs::write(&path, encode_entry(&entry(&game, "g1f3", 1))).unwrap();
+
+    let book = Book::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(book.len(), 1);
+    assert_eq!(book.best_move(&game).unwrap().to, square("f3"));
+
+    assert!(Book::from_bytes(&[0; 15]).is_err());
+}
+
+#[test]
+fn builds_books_from_pgn() {
+    let games = chess_engine::pgn::parse(
+        r#"[White "Alice"] [Black "Bob"] [Result "1-0"] 1. e4 e5 2. Nf3 1-0
+[White "Bob"] [Black "Alice"] [Result "1/2-1/2"] 1. e4 c5 1/2-1/2
+[White "Carl"] [Black "Alice"] [Result "0-1"] 1. d4 d5 0-1
+[White "Carl"] [Black "Bob"] 1. c4 *"#,
+    );
+
+    let mut builder = BookBuilder::new();
+    builder.min_games = 1;
+    for game in &games {
+        builder.add_game(game);
+    }
+
+    let mut bytes = Vec::new();
+    assert_eq!(builder.write(&mut bytes).unwrap(), 4);
+
+    // 1. d4 only ever lost and 1. c4 has no result, e4 won once and drew once
+    let start = Book::from_bytes(&bytes).unwrap().moves(&Game::new());
+    assert_eq!(start.len(), 1);
+    assert_eq!((start[0].to, start[0].weight), (square("e4"), 3));
+
+    let mut builder = BookBuilder::new();
+    builder.min_games = 1;
+    builder.player = Some("alice".to_owned());
+    for game in &games {
+        builder.add_game(game);
+    }
+
+    let alice = book(&builder.entries());
+    assert_eq!(alice.best_move(&Game::new()).unwrap().weight, 2);
+    // Bob's 1... e5 is left out, Alice's own replies to 1. e4 and 1. d4 are in
+    let replies = alice.moves(&play(&["e2e4"]));
+    assert_eq!(replies.len(), 1);
+    assert_eq!(replies[0].to, square("c5"));
+    assert_eq!(alice.best_move(&play(&["d2d4"])).unwrap().to, square("d5"));
+
+    builder.min_games = 2;
+    assert!(builder.entries().is_empty());
+}
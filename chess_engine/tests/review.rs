@@ -0,0 +1,80 @@
+use chess_engine::pgn;
+use chess_engine::piece::Color;
+use chess_engine::review::*;
+use chess_engine::uci::{parse_move, Engine, Limit, UciMove};
+
+fn engine() -> Engine {
+    Engine::spawn(env!("CARGO_BIN_EXE_fake_engine")).unwrap()
+}
+
+fn moves(text: &str) -> Vec<UciMove> {
+    text.split_whitespace()
+        .map(|mv| parse_move(mv).unwrap())
+        .collect()
+}
+
+#[test]
+fn classifies_losses() {
+    assert_eq!(Judgement::from_loss(49), None);
+    assert_eq!(Judgement::from_loss(50), Some(Judgement::Inaccuracy));
+    assert_eq!(Judgement::from_loss(150), Some(Judgement::Mistake));
+    assert_eq!(Judgement::from_loss(300), Some(Judgement::Blunder));
+}
+
+#[test]
+fn finds_blunders_and_better_moves() {
+    // The fake engine wants 2... Nc6, 2... f6 gives white 400 centipawns
+    let played = moves("e2e4 e7e5 g1f3 f7f6");
+    let review = review(&mut engine(), &played, Limit::Depth(1)).unwrap();
+
+    let sans: Vec<&str> = review.moves.iter().map(|r| r.san.as_str()).collect();
+    assert_eq!(sans, vec!["e4", "e5", "Nf3", "f6"]);
+
+    for good in &review.moves[..3] {
+        assert_eq!(good.loss, 0);
+        assert_eq!(good.judgement, None);
+    }
+
+    let blunder = &review.moves[3];
+    assert_eq!(blunder.color, Color::Black);
+    assert_eq!((blunder.before, blunder.after), (10, -410));
+    assert_eq!(blunder.judgement, Some(Judgement::Blunder));
+    assert_eq!(blunder.best_san.as_deref(), Some("Nc6"));
+
+    let [white, black] = review.accuracy;
+    assert!(white > 90.0, "{}", white);
+    assert!(black < white, "{} {}", black, white);
+}
+
+#[test]
+fn writes_annotated_pgn() {
+    let played = moves("e2e4 e7e5 g1f3 f7f6");
+    let review = review(&mut engine(), &played, Limit::Depth(1)).unwrap();
+
+    let tags = vec![
+        ("White".to_owned(), "Alice".to_owned()),
+        ("Black".to_owned(), "Bob \"B\"".to_owned()),
+    ];
+    let text = review.to_pgn(&tags, "*");
+
+    let movetext: Vec<&str> = text.split_whitespace().collect();
+    assert!(
+        movetext
+            .join(" ")
+            .contains("2. Nf3 f6 $4 {Blunder. Nc6 was best.} *"),
+        "{}",
+        text
+    );
+    assert!(text.lines().all(|line| line.len() < 80));
+
+    let games = pgn::parse(&text);
+    assert_eq!(games.len(), 1);
+    assert_eq!(games[0].tag("Black"), Some("Bob \"B\""));
+    assert_eq!(games[0].moves, vec!["e4", "e5", "Nf3", "f6"]);
+    assert_eq!(games[0].result.as_deref(), Some("*"));
+}
+
+#[test]
+fn rejects_illegal_games() {
+    assert!(review(&mut engine(), &moves("e2e4 e2e4"), Limit::Depth(1)).is_err());
+}
@@ -0,0 +1,186 @@
+use chess_engine::game::Game;
+use chess_engine::piece::{Color, Piece, PieceType};
+use chess_engine::uci::parse_move;
+
+use Color::*;
+use PieceType::*;
+
+fn position(pieces: &[(&str, PieceType, Color)]) -> Game {
+    let mut game = Game::new();
+    for column in game.board.board_squares.iter_mut() {
+        for square in column.iter_mut() {
+            square.piece = None;
+        }
+    }
+
+    for (name, kind, color) in pieces {
+        game.board.board_squares[square(name).0][square(name).1].piece =
+            Some(Piece::new(*kind, *color));
+    }
+
+    game
+}
+
+fn square(name: &str) -> (usize, usize) {
+    let bytes = name.as_bytes();
+    ((bytes[0] - b'a') as usize, (bytes[1] - b'1') as usize)
+}
+
+// Checks see() and that see_ge() agrees with it around the answer
+fn check(pieces: &[(&str, PieceType, Color)], from: &str, to: &str, expected: i32) {
+    let game = position(pieces);
+    let mv = parse_move(&format!("{}{}", from, to)).unwrap();
+
+    assert_eq!(game.see(&mv), expected, "{}", mv);
+    for threshold in &[expected - 1, expected, expected + 1] {
+        assert_eq!(
+            game.see_ge(&mv, *threshold),
+            expected >= *threshold,
+            "{} threshold {}",
+            mv,
+            threshold
+        );
+    }
+}
+
+#[test]
+fn simple_captures() {
+    let kings = [("a1", King, White), ("h8", King, Black)];
+
+    // Undefended pawn
+    let mut pieces = kings.to_vec();
+    pieces.extend(&[("e1", Rook, White), ("e5", Pawn, Black)]);
+    check(&pieces, "e1", "e5", 100);
+
+    // Pawn defended by a pawn
+    let mut pieces = kings.to_vec();
+    pieces.extend(&[
+        ("f4", Knight, White),
+        ("d5", Pawn, Black),
+        ("e6", Pawn, Black),
+    ]);
+    check(&pieces, "f4", "d5", -200);
+
+    // Queen defended by a pawn is still worth taking with a knight
+    let mut pieces = kings.to_vec();
+    pieces.extend(&[
+        ("f4", Knight, White),
+        ("d5", Queen, Black),
+        ("e6", Pawn, Black),
+    ]);
+    check(&pieces, "f4", "d5", 600);
+
+    // Quiet move onto a square a pawn covers
+    let mut pieces = kings.to_vec();
+    pieces.extend(&[("f3", Knight, White), ("h6", Pawn, Black)]);
+    check(&pieces, "f3", "g5", -300);
+
+    // Quiet move onto a safe square
+    check(&pieces, "f3", "e5", 0);
+}
+
+#[test]
+fn recaptures_in_order() {
+    // Pawn defended by knight and rook, attacked by bishop and rook. White
+    // stops before giving up the rook and is a bishop for a pawn down
+    let pieces = [
+        ("a1", King, White),
+        ("h8", King, Black),
+        ("b2", Bishop, White),
+        ("e1", Rook, White),
+        ("e5", Pawn, Black),
+        ("c6", Knight, Black),
+        ("e8", Rook, Black),
+    ];
+    check(&pieces, "b2", "e5", 100 - 300);
+    check(&pieces, "e1", "e5", 100 - 500);
+
+    // The defender stops when recapturing loses material
+    let pieces = [
+        ("a1", King, White),
+        ("h8", King, Black),
+        ("d1", Rook, White),
+        ("a2", Bishop, White),
+        ("d5", Knight, Black),
+        ("d8", Queen, Black),
+    ];
+    check(&pieces, "d1", "d5", 300);
+}
+
+#[test]
+fn x_ray_attackers_join_in() {
+    let kings = [("a1", King, White), ("h8", King, Black)];
+
+    // Doubled rooks against a defended pawn
+    let mut pieces = kings.to_vec();
+    pieces.extend(&[
+        ("e1", Rook, White),
+        ("e2", Rook, White),
+        ("e5", Pawn, Black),
+        ("e8", Rook, Black),
+    ]);
+    check(&pieces, "e2", "e5", 100);
+
+    // Without the second rook it loses the exchange
+    pieces.retain(|(name, _, _)| *name != "e1");
+    check(&pieces, "e2", "e5", -400);
+
+    // Queen behind a bishop on the diagonal, knight for bishop and then the
+    // queen wins the pawn
+    let mut pieces = kings.to_vec();
+    pieces.extend(&[
+        ("b1", Queen, White),
+        ("c2", Bishop, White),
+        ("e4", Knight, Black),
+        ("f5", Pawn, Black),
+    ]);
+    check(&pieces, "c2", "e4", 100);
+
+    // Doubled rooks defending, the second one wins the last exchange
+    let mut pieces = kings.to_vec();
+    pieces.extend(&[
+        ("f3", Knight, White),
+        ("e1", Rook, White),
+        ("e5", Pawn, Black),
+        ("e7", Rook, Black),
+        ("e8", Rook, Black),
+    ]);
+    check(&pieces, "f3", "e5", 100 - 300);
+
+    pieces.retain(|(name, _, _)| *name != "e8");
+    check(&pieces, "f3", "e5", 100);
+}
+
+#[test]
+fn kings_and_special_moves() {
+    // The king can only recapture when nothing else covers the square
+    let mut pieces = vec![
+        ("a1", King, White),
+        ("d1", Queen, White),
+        ("d5", Pawn, Black),
+        ("e6", King, Black),
+    ];
+    check(&pieces, "d1", "d5", 100 - 900);
+
+    pieces.push(("c4", Pawn, White));
+    check(&pieces, "d1", "d5", 100);
+
+    // Also when the square is covered by an x-ray through the queen
+    pieces.retain(|(name, _, _)| *name != "c4" && *name != "d1");
+    pieces.extend(&[("d2", Queen, White), ("d1", Rook, White)]);
+    check(&pieces, "d2", "d5", 100);
+
+    // En passant takes the pawn beside the capturing pawn
+    let pieces = [
+        ("a1", King, White),
+        ("h8", King, Black),
+        ("e5", Pawn, White),
+        ("d5", Pawn, Black),
+    ];
+    check(&pieces, "e5", "d6", 100);
+
+    // Castling and empty squares are neutral
+    let game = position(&[("e1", King, White), ("h1", Rook, White)]);
+    assert_eq!(game.see(&parse_move("e1g1").unwrap()), 0);
+    assert_eq!(game.see(&parse_move("c3c4").unwrap()), 0);
+}
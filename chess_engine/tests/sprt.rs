@@ -0,0 +1,52 @@
+use chess_engine::sprt::*;
+
+fn score(wins: u32, draws: u32, losses: u32) -> MatchScore {
+    MatchScore {
+        wins,
+        draws,
+        losses,
+    }
+}
+
+#[test]
+fn estimates_elo() {
+    assert_eq!(score(0, 0, 0).elo(), None);
+    assert_eq!(score(3, 0, 0).elo(), None);
+
+    let (diff, margin) = score(5, 2, 5).elo().unwrap();
+    assert!(diff.abs() < 1e-9);
+    assert!(margin > 0.0);
+
+    let (diff, _) = score(3, 0, 1).elo().unwrap();
+    assert!((diff - 190.85).abs() < 0.01);
+
+    // More games give a narrower interval
+    let (_, few) = score(6, 4, 2).elo().unwrap();
+    let (_, many) = score(60, 40, 20).elo().unwrap();
+    assert!(many < few);
+}
+
+#[test]
+fn converts_between_elo_and_score() {
+    assert!((expected_score(0.0) - 0.5).abs() < 1e-9);
+    assert!((elo(expected_score(123.0)) - 123.0).abs() < 1e-9);
+}
+
+#[test]
+fn decides_tests() {
+    let sprt = Sprt {
+        elo0: 0.0,
+        elo1: 10.0,
+        alpha: 0.05,
+        beta: 0.05,
+    };
+
+    let (lower, upper) = sprt.bounds();
+    assert!((lower + 2.944).abs() < 0.001);
+    assert!((upper - 2.944).abs() < 0.001);
+
+    assert_eq!(sprt.verdict(&score(0, 0, 0)), Verdict::Undecided);
+    assert_eq!(sprt.verdict(&score(100, 100, 100)), Verdict::Undecided);
+    assert_eq!(sprt.verdict(&score(600, 200, 200)), Verdict::Accepted);
+    assert_eq!(sprt.verdict(&score(200, 200, 600)), Verdict::Rejected);
+}
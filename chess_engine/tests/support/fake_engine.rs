@@ -0,0 +1,99 @@
+// Minimal UCI engine for the tests. It plays a fixed opening and reports one
+// info line per depth, infinite and ponder searches only answer once they're
+// stopped or hit. Leaving the script with the last move hands the side to
+// move 400 centipawns. With the Ponder option on it suggests the next move
+// of the script to ponder on
+use std::io::{self, BufRead, Write};
+
+const SCRIPT: [&str; 4] = ["e2e4", "e7e5", "g1f3", "b8c6"];
+
+fn main() {
+    let stdin = io::stdin();
+    let mut out = io::stdout();
+    let mut played = 0;
+    let mut bonus = 0;
+    let mut searching = false;
+    let mut ponder = false;
+
+    for line in stdin.lock().lines() {
+        let line = line.unwrap();
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let reply = SCRIPT.get(played).copied().unwrap_or("(none)");
+        let bestmove = match SCRIPT.get(played + 1) {
+            Some(next) if ponder && reply != "(none)" => {
+                format!("bestmove {} ponder {}", reply, next)
+            }
+            _ => format!("bestmove {}", reply),
+        };
+
+        match tokens.first().copied() {
+            Some("uci") => {
+                writeln!(out, "id name Fake Engine 1.0").unwrap();
+                writeln!(out, "id author Nobody").unwrap();
+                writeln!(out, "option name Hash type spin default 16 min 1 max 64").unwrap();
+                writeln!(out, "option name Ponder type check default false").unwrap();
+                writeln!(out, "uciok").unwrap();
+            }
+            Some("isready") => writeln!(out, "readyok").unwrap(),
+            Some("setoption") => {
+                if let [_, "name", "Ponder", "value", value] = tokens[..] {
+                    ponder = value == "true";
+                }
+            }
+            Some("position") => {
+                let moves: Vec<&str> = tokens
+                    .iter()
+                    .skip_while(|t| **t != "moves")
+                    .skip(1)
+                    .copied()
+                    .collect();
+
+                played = moves.len();
+                bonus = match moves.last() {
+                    Some(last) if SCRIPT.get(played - 1) != Some(last) => 400,
+                    _ => 0,
+                };
+            }
+            Some("go") => {
+                let pondering = tokens.get(1) == Some(&"ponder");
+                let limit = if pondering {
+                    &tokens[2..]
+                } else {
+                    &tokens[1..]
+                };
+                let depth = match limit.get(0..2) {
+                    Some(["depth", depth]) => depth.parse().unwrap(),
+                    _ => 1,
+                };
+
+                writeln!(out, "info string searching {} plies in", played).unwrap();
+                for d in 1..=depth {
+                    writeln!(
+                        out,
+                        "info depth {} seldepth {} score cp {} nodes {} pv {}",
+                        d,
+                        d,
+                        10 * d + bonus,
+                        100 * d,
+                        reply
+                    )
+                    .unwrap();
+                }
+
+                if pondering || limit.first() == Some(&"infinite") {
+                    searching = true;
+                } else {
+                    writeln!(out, "{}", bestmove).unwrap();
+                }
+            }
+            Some("stop") | Some("ponderhit") if searching => {
+                searching = false;
+                writeln!(out, "{}", bestmove).unwrap();
+            }
+            Some("quit") => return,
+            _ => {}
+        }
+
+        out.flush().unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+use chess_engine::game::Game;
+use chess_engine::piece::{Color, PieceType};
+use chess_engine::uci::*;
+
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn engine() -> Engine {
+    Engine::spawn(env!("CARGO_BIN_EXE_fake_engine")).unwrap()
+}
+
+fn mv(text: &str) -> UciMove {
+    parse_move(text).unwrap()
+}
+
+#[test]
+fn parses_and_formats_moves() {
+    let promotion = mv("e7e8q");
+    assert_eq!((promotion.from, promotion.to), ((4, 6), (4, 7)));
+    assert_eq!(promotion.promotion, Some(PieceType::Queen));
+    assert_eq!(promotion.to_string(), "e7e8q");
+    assert_eq!(mv("g1f3").to_string(), "g1f3");
+
+    for bad in &["", "e2", "e2e9", "i2e4", "e7e8k", "e2e4e5", "(none)"] {
+        assert_eq!(parse_move(bad), None, "{}", bad);
+    }
+}
+
+#[test]
+fn parses_info_lines() {
+    let info =
+        parse_info("info depth 12 seldepth 18 multipv 1 score mate -3 nodes 4096 nps 1000 time 52 pv e2e4 e7e5 g1f3")
+            .unwrap();
+
+    assert_eq!(info.depth, Some(12));
+    assert_eq!(info.multipv, Some(1));
+    assert_eq!(info.score, Some(Score::Mate(-3)));
+    assert_eq!(info.nodes, Some(4096));
+    assert_eq!(info.time, Some(52));
+    assert_eq!(info.pv, vec![mv("e2e4"), mv("e7e5"), mv("g1f3")]);
+
+    let bound = parse_info("info score cp -25 upperbound depth 3").unwrap();
+    assert_eq!(bound.score, Some(Score::Centipawns(-25)));
+    assert_eq!(bound.depth, Some(3));
+
+    assert_eq!(parse_info("info string hello depth 3"), None);
+    assert_eq!(parse_info("bestmove e2e4"), None);
+}
+
+#[test]
+fn plays_moves_on_games() {
+    let mut game = Game::new();
+
+    assert!(!play_move(&mut game, &mv("e7e5")));
+    assert!(!play_move(&mut game, &mv("e2e5")));
+    assert!(play_move(&mut game, &mv("e2e4")));
+    assert_eq!(game.curr_player, Color::Black);
+    assert!(game.board.board_squares[4][3].piece.is_some());
+}
+
+#[test]
+fn handshakes_and_searches() {
+    let mut engine = engine();
+    assert_eq!(engine.name, "Fake Engine 1.0");
+    assert_eq!(engine.author, "Nobody");
+
+    engine.new_game().unwrap();
+    engine.position(&[]).unwrap();
+    engine.go(Limit::Depth(3)).unwrap();
+    assert!(engine.is_searching());
+
+    for depth in 1..=3 {
+        match engine.next_event(TIMEOUT) {
+            Some(EngineEvent::Info(info)) => {
+                assert_eq!(info.depth, Some(depth));
+                assert_eq!(info.score, Some(Score::Centipawns(10 * depth as i32)));
+                assert_eq!(info.pv, vec![mv("e2e4")]);
+            }
+            other => panic!("expected info, got {:?}", other),
+        }
+    }
+
+    assert_eq!(
+        engine.next_event(TIMEOUT),
+        Some(EngineEvent::BestMove {
+            best: Some(mv("e2e4")),
+            ponder: None
+        })
+    );
+    assert!(!engine.is_searching());
+}
+
+#[test]
+fn drops_output_of_stopped_searches() {
+    let mut engine = engine();
+
+    engine.position(&[]).unwrap();
+    engine.go(Limit::Infinite).unwrap();
+    engine.stop().unwrap();
+
+    engine.position(&[mv("e2e4")]).unwrap();
+    engine.go(Limit::MoveTime(10)).unwrap();
+
+    let mut events = Vec::new();
+    while let Some(event) = engine.next_event(TIMEOUT) {
+        let done = matches!(event, EngineEvent::BestMove { .. });
+        events.push(event);
+
+        if done {
+            break;
+        }
+    }
+
+    let best = mv("e7e5");
+    assert_eq!(events.len(), 2, "{:?}", events);
+    assert!(matches!(&events[0], EngineEvent::Info(info) if info.pv == vec![best]));
+    assert_eq!(
+        events[1],
+        EngineEvent::BestMove {
+            best: Some(best),
+            ponder: None
+        }
+    );
+}
+
+#[test]
+fn ponders() {
+    let mut engine = engine();
+    engine.set_option("Ponder", Some("true")).unwrap();
+
+    engine.position(&[]).unwrap();
+    engine.go(Limit::Depth(1)).unwrap();
+    let (best, _) = engine.finish_search(TIMEOUT).unwrap();
+    assert_eq!(best, Some(mv("e2e4")));
+
+    // Ponder on the move the engine expects, then have it played
+    engine.position(&[mv("e2e4"), mv("e7e5")]).unwrap();
+    engine.go_ponder(Limit::MoveTime(10)).unwrap();
+    assert!(engine.is_pondering());
+    assert!(!matches!(
+        engine.next_event(Duration::from_millis(100)),
+        Some(EngineEvent::BestMove { .. })
+    ));
+
+    engine.ponderhit().unwrap();
+    assert!(!engine.is_pondering());
+    assert!(engine.is_searching());
+    assert_eq!(engine.finish_search(TIMEOUT).unwrap().0, Some(mv("g1f3")));
+
+    // A different move stops the ponder search and its bestmove is dropped
+    engine
+        .position(&[mv("e2e4"), mv("e7e5"), mv("g1f3")])
+        .unwrap();
+    engine.go_ponder(Limit::MoveTime(10)).unwrap();
+    engine.stop().unwrap();
+    assert!(!engine.is_pondering());
+
+    engine
+        .position(&[mv("e2e4"), mv("e7e5"), mv("g1f3"), mv("g8f6")])
+        .unwrap();
+    engine.go(Limit::Depth(1)).unwrap();
+
+    // The fake engine is out of moves here, unlike for the stopped search
+    assert_eq!(engine.finish_search(TIMEOUT).unwrap().0, None);
+}
+
+#[test]
+fn reports_missing_engines() {
+    assert!(Engine::spawn("./no-such-engine").is_err());
+}
@@ -4,17 +4,38 @@ use crate::tile_widget::*;
 
 use crate::*;
 
+use chess_engine::clock::{Clock, RealTime, TimeControl, Timing};
+use chess_engine::fen;
 use chess_engine::game::*;
 use chess_engine::piece::{Color as PieceColor, Piece, PieceType};
+use chess_engine::polyglot;
+use chess_engine::review;
+use chess_engine::uci::{self, Engine, EngineEvent, Info, Limit, Score, UciMove};
 use orbtk::prelude::*;
 
 use std::collections::HashSet;
 use std::collections::VecDeque;
+use std::io;
+use std::sync::mpsc::{self, TryRecvError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 pub const default_tiles: (&str, &str) = (colors::LINK_WATER_COLOR, colors::SLATE_GRAY_COLOR);
 pub const walkable_tiles: (&str, &str) = ("#66ff66", "#33cc33");
 pub const attackable_tiles: (&str, &str) = ("#ff3300", "#991f00");
 pub const selected_tile: (&str, &str) = ("#ffff00", "#cccc00");
+pub const BEST_MOVE_TILES: (&str, &str) = ("#66ccff", "#3399cc");
+
+// How long the engine gets for each of its moves, in milliseconds
+const ENGINE_MOVE_TIME: u64 = 1000;
+const REVIEW_MOVE_TIME: u64 = 200;
+// The most time a network move may take to arrive without it being charged
+// to the side that made it
+const MAX_LAG: Duration = Duration::from_secs(2);
+// How long a network game is kept after the connection drops
+const RECONNECT_GRACE: Duration = Duration::from_secs(60);
+// How long the peer gets to agree on a game once connected
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const HOST_ADDR: &str = "127.0.0.1:80";
 
 fn piece_to_char(color: PieceColor, kind: PieceType) -> String {
     let a = if color == PieceColor::Black { "B" } else { "W" };
@@ -39,6 +60,14 @@ pub enum Action {
     Connect,
     ShowPromotion(),
     PromoteTile(PieceType),
+    StartEngine(EngineRole),
+    StartGame,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EngineRole {
+    Opponent(PieceColor),
+    Analysis,
 }
 
 #[derive(AsAny)]
@@ -52,6 +81,49 @@ pub struct ChessState {
     ipbox: Option<Entity>,
     ip: String,
     network: Option<ChessNet>,
+    engineevents: VecDeque<EngineEvent>,
+    enginebox: Option<Entity>,
+    engine_path: String,
+    // The engine finished games are reviewed with, None until one has been
+    // set with CHESS_ENGINE or started from the menu
+    review_engine: Option<String>,
+    engine: Option<Engine>,
+    engine_role: Option<EngineRole>,
+    // The engine being started off the UI thread and the role it's for
+    starting_engine: Option<(EngineRole, mpsc::Receiver<io::Result<Engine>>)>,
+    // Plies played when the engine last started searching
+    searched: Option<usize>,
+    // The player's move the engine expects and is thinking on meanwhile
+    pondering: Option<UciMove>,
+    analysis: Option<Info>,
+    moves: Vec<UciMove>,
+    tcbox: Option<Entity>,
+    time_control: String,
+    // None when the game is untimed or over
+    clock: Option<Clock>,
+    // What the white and black clock displays show
+    clock_text: [String; 2],
+    // Until both sides of a network game agree on the time control
+    negotiating: bool,
+    // When to give up on the peer agreeing, while negotiating over a
+    // connection
+    handshake_deadline: Option<Instant>,
+    // The time the peer had left after the move being played
+    peer_time: Option<Duration>,
+    // Waiting for the peer to confirm its time has run out
+    flag_claimed: bool,
+    // The side played over the network, once both sides have agreed on it
+    color: Option<PieceColor>,
+    // The side the host picked, proposed to the client
+    host_color: PieceColor,
+    peer: Option<Hello>,
+    // The game the host proposes once it knows what the client can do
+    proposal: Option<Setup>,
+    // The network game being played, None before it's agreed and once it's
+    // over
+    session: Option<u64>,
+    start_fen: String,
+    reconnecting: Option<Reconnect>,
 }
 
 impl Default for ChessState {
@@ -66,20 +138,67 @@ impl Default for ChessState {
             attackable: None,
             popup: None,
             ipbox: None,
-            ip: "127.0.0.1:80".to_owned(),
+            ip: HOST_ADDR.to_owned(),
             network: None,
+            engineevents: VecDeque::new(),
+            enginebox: None,
+            engine_path: std::env::var("CHESS_ENGINE").unwrap_or_else(|_| "stockfish".to_owned()),
+            review_engine: std::env::var("CHESS_ENGINE").ok(),
+            engine: None,
+            engine_role: None,
+            starting_engine: None,
+            searched: None,
+            pondering: None,
+            analysis: None,
+            moves: Vec::new(),
+            tcbox: None,
+            time_control: "".to_owned(),
+            clock: None,
+            clock_text: ["".to_owned(), "".to_owned()],
+            negotiating: false,
+            handshake_deadline: None,
+            peer_time: None,
+            flag_claimed: false,
+            color: None,
+            host_color: PieceColor::White,
+            peer: None,
+            proposal: None,
+            session: None,
+            start_fen: fen::START.to_owned(),
+            reconnecting: None,
         }
     }
 }
 
 impl State for ChessState {
     fn update(&mut self, _: &mut Registry, ctx: &mut Context) {
+        self.show_clocks(ctx);
+        self.check_flag();
+
+        let reconnecting = self.reconnecting.is_some();
+        self.poll_reconnect();
         self.poll_network();
+        self.check_handshake();
+
+        if !self.netevents.is_empty() {
+            while self.netevents.len() > 0 {
+                let event = self.netevents.pop_front().unwrap();
+
+                self.handle_netevent(event);
+            }
+
+            self.update_backgrounds(ctx);
+        }
+
+        if self.reconnecting.is_some() != reconnecting {
+            self.show_analysis(ctx);
+        }
 
-        while self.netevents.len() > 0 {
-            let event = self.netevents.pop_front().unwrap();
+        self.poll_engine_start();
+        self.poll_engine();
 
-            self.handle_netevent(event);
+        while let Some(event) = self.engineevents.pop_front() {
+            self.handle_engine_event(ctx, event);
         }
 
         while self.actions.len() > 0 {
@@ -104,9 +223,17 @@ impl State for ChessState {
                     }
                     let build = &mut ctx.build_context();
 
-                    let (ipbox, popup) = popup_start(current_entity, build, self.ip.clone());
+                    let (ipbox, enginebox, tcbox, popup) = popup_start(
+                        current_entity,
+                        build,
+                        self.ip.clone(),
+                        self.engine_path.clone(),
+                        self.time_control.clone(),
+                    );
                     self.popup = Some(popup);
                     self.ipbox = Some(ipbox);
+                    self.enginebox = Some(enginebox);
+                    self.tcbox = Some(tcbox);
 
                     build.append_child(current_entity, popup);
 
@@ -117,7 +244,29 @@ impl State for ChessState {
                     self.network = None;
                     self.netevents.clear();
 
+                    self.engine = None;
+                    self.engine_role = None;
+                    self.starting_engine = None;
+                    self.engineevents.clear();
+                    self.searched = None;
+                    self.pondering = None;
+                    self.analysis = None;
+                    self.moves.clear();
+
+                    self.clock = None;
+                    self.negotiating = false;
+                    self.peer_time = None;
+                    self.flag_claimed = false;
+                    self.color = None;
+                    self.peer = None;
+                    self.proposal = None;
+                    self.session = None;
+                    self.start_fen = fen::START.to_owned();
+                    self.reconnecting = None;
+                    self.set_clock_text(ctx, ["".to_owned(), "".to_owned()]);
+
                     self.update_backgrounds(ctx);
+                    self.show_analysis(ctx);
                 }
                 Action::ClosePopups => {
                     if let Some(popup) = self.popup {
@@ -145,10 +294,12 @@ impl State for ChessState {
 
                     let history = &self.board.board.history;
                     let (from, to) = (&history[history.len() - 2], &history[history.len() - 1]);
+                    let (from, to) = ((from[0], from[1]), (to[0], to[1]));
 
+                    self.record_move(from, to, Some(kind));
                     self.send_move(MoveEvent::Promotion(
-                        encode_index((from[0], from[1])),
-                        encode_index((to[0], to[1])),
+                        encode_index(from),
+                        encode_index(to),
                         encode_piece(kind),
                     ));
                 }
@@ -161,8 +312,63 @@ impl State for ChessState {
                         self.connect();
                     }
                 }
+                Action::StartEngine(role) => {
+                    if let Some(enginebox) = self.enginebox {
+                        let child = ctx.get_widget(enginebox);
+                        let textctx = TextBox::get(child);
+
+                        self.engine_path = textctx.text().as_string();
+                        self.start_engine(role);
+                    }
+                }
+                Action::StartGame => {
+                    if let Some(tcbox) = self.tcbox {
+                        let child = ctx.get_widget(tcbox);
+                        let textctx = TextBox::get(child);
+
+                        self.time_control = textctx.text().as_string().trim().to_owned();
+                    }
+
+                    let control = if self.time_control.is_empty() {
+                        None
+                    } else {
+                        let control = TimeControl::parse(&self.time_control);
+                        if control.is_none() {
+                            println!("couldn't read time control {}", self.time_control);
+                        }
+                        control
+                    };
+
+                    self.clock = control.map(|control| Clock::new(control, RealTime::new()));
+
+                    // Both sides play the host's game, which starts once the
+                    // client has agreed to it
+                    if self.network.as_ref().is_some_and(|net| net.host) {
+                        let time_control = self
+                            .clock
+                            .as_ref()
+                            .map_or("".to_owned(), |c| c.control.to_string());
+
+                        self.proposal = Some(Setup {
+                            host_color: self.host_color,
+                            variant: VARIANTS[0].to_owned(),
+                            fen: fen::START.to_owned(),
+                            time_control,
+                            session: new_session_id(),
+                        });
+                        self.negotiating = true;
+                        self.propose();
+                    } else if self.starting_engine.is_none() {
+                        self.start_clock();
+                    }
+                }
             }
         }
+
+        if self.engine_think() {
+            self.show_analysis(ctx);
+            self.update_backgrounds(ctx);
+        }
     }
 
     fn init(&mut self, _registry: &mut Registry, _ctx: &mut Context) {
@@ -179,78 +385,453 @@ impl ChessState {
     pub fn connect(&mut self) {
         if let Ok(res) = ChessNet::connect(self.ip.clone()) {
             self.network = Some(res);
+
+            // Until the host says what game is played
+            self.clock = None;
+            self.negotiating = true;
         }
     }
 
-    pub fn host(&mut self) {
-        if let Ok(res) = ChessNet::host("127.0.0.1:80".to_owned()) {
+    pub fn host(&mut self, color: PieceColor) {
+        self.host_color = color;
+
+        if let Ok(res) = ChessNet::host(HOST_ADDR.to_owned()) {
             self.network = Some(res);
         }
     }
 
     pub fn handle_netevent(&mut self, e: NetEvent) {
         match e {
-            NetEvent::Move(mv) => match mv {
-                MoveEvent::Standard(p1, p2) => {
-                    let from = parse_index(p1);
-                    let to = parse_index(p2);
+            NetEvent::Move(mv, time) => {
+                self.peer_time = time;
+                self.play_net_move(mv);
+                self.peer_time = None;
+            }
+            NetEvent::Checkmate => {
+                let team = match self.board.curr_player {
+                    PieceColor::White => "Black",
+                    PieceColor::Black => "White",
+                };
 
-                    if self
-                        .board
-                        .get_available_moves(from)
-                        .contains(&vec![to.0, to.1])
-                    {
-                        self.board.move_piece(from, to);
-                    } else {
-                        self.send(NetEvent::Decline);
+                self.stop_clock();
+                self.session = None;
+                self.action(Action::ClosePopups);
+                self.action(Action::VictoryRoyale(format!("{} wins", team)));
+            }
+            NetEvent::Hello(hello) => {
+                println!(
+                    "connected to {}, protocol version {}, features {}",
+                    hello.name,
+                    hello.version,
+                    hello.features.join(" ")
+                );
+
+                if hello.version != PROTOCOL_VERSION {
+                    self.reject(format!(
+                        "{} speaks protocol version {}, this build speaks {}",
+                        hello.name, hello.version, PROTOCOL_VERSION
+                    ));
+                    return;
+                }
+
+                self.peer = Some(hello);
+                self.propose();
+            }
+            NetEvent::Setup(setup) => self.accept_setup(setup),
+            NetEvent::Accept if self.negotiating => {
+                if let Some(setup) = self.proposal.take() {
+                    self.color = Some(setup.host_color);
+                    self.session = Some(setup.session);
+                    self.start_fen = setup.fen;
+                }
+                self.negotiating = false;
+                self.start_clock();
+            }
+            NetEvent::Resume(theirs) => self.resume(theirs),
+            NetEvent::Sync(host) => self.resync(host),
+            NetEvent::Reject(reason) => {
+                let name = self.peer.as_ref().map_or("The other side", |p| &p.name);
+                let text = format!("{} won't play: {}", name, reason);
+
+                self.network_error(text);
+            }
+            // Only this side can tell if its own time is up, the peer's copy of
+            // its clock runs ahead by however long moves take to arrive
+            NetEvent::ClaimFlag => {
+                let color = match self.network_color() {
+                    Some(color) => color,
+                    None => return,
+                };
+
+                match self.clock.as_ref() {
+                    Some(clock) if clock.flagged() == Some(color) => {
+                        self.send(NetEvent::FlagFell);
+                        self.end_on_time(color);
                     }
+                    Some(clock) => {
+                        let time = clock.remaining(color);
+                        self.send(NetEvent::Time(time));
+                    }
+                    None => {}
+                }
+            }
+            NetEvent::FlagFell => {
+                if let (Some(color), Some(_)) = (self.network_color(), self.clock.as_ref()) {
+                    self.end_on_time(color.other());
+                }
+            }
+            NetEvent::Time(time) => {
+                if let (Some(color), Some(clock)) = (self.network_color(), self.clock.as_mut()) {
+                    clock.correct(color.other(), time, MAX_LAG);
+                }
+                self.flag_claimed = false;
+            }
+            NetEvent::Disconnect => match self.session {
+                Some(_) => self.lost_connection(),
+                None => self.action(Action::Restart),
+            },
+            NetEvent::Decline => {
+                self.action(Action::Restart);
+            }
+            _ => {}
+        }
+    }
+
+    // Sends the host's proposal once the client has said what it can do
+    fn propose(&mut self) {
+        let (peer, setup) = match (&self.peer, &self.proposal) {
+            (Some(peer), Some(setup)) => (peer, setup),
+            _ => return,
+        };
+
+        let missing: Vec<&str> = setup
+            .required_features()
+            .into_iter()
+            .filter(|feature| !peer.features.iter().any(|f| f == feature))
+            .collect();
+        if !missing.is_empty() {
+            let reason = format!("{} can't play with {}", peer.name, missing.join(", "));
+            self.reject(reason);
+            return;
+        }
+
+        let setup = setup.clone();
+        self.send(NetEvent::Setup(setup));
+    }
+
+    // The client's side of agreeing on the game, anything it can't play is
+    // rejected
+    fn accept_setup(&mut self, setup: Setup) {
+        if self.network.as_ref().is_some_and(|net| net.host) {
+            self.reject("only the host proposes the game".to_owned());
+            return;
+        }
+
+        if self.peer.is_none() {
+            self.reject("the host didn't say which version it is".to_owned());
+            return;
+        }
+
+        if !VARIANTS.contains(&setup.variant.as_str()) {
+            self.reject(format!("variant {} isn't supported", setup.variant));
+            return;
+        }
+
+        let board = match fen::parse(&setup.fen) {
+            Some(board) => board,
+            None => {
+                self.reject(format!("couldn't read the position {}", setup.fen));
+                return;
+            }
+        };
+
+        let control = TimeControl::parse(&setup.time_control);
+        if control.is_none() && !setup.time_control.is_empty() {
+            self.reject(format!("couldn't read time control {}", setup.time_control));
+            return;
+        }
+
+        self.board = board;
+        self.color = Some(setup.host_color.other());
+        self.session = Some(setup.session);
+        self.start_fen = setup.fen;
+        self.clock = control.map(|control| Clock::new(control, RealTime::new()));
+        self.negotiating = false;
+        self.start_clock();
+        self.send(NetEvent::Accept);
+    }
+
+    // Turns the peer down and hangs up, the reason is shown on both sides
+    fn reject(&mut self, reason: String) {
+        self.send(NetEvent::Reject(reason.clone()));
+        self.network_error(format!("Can't play: {}", reason));
+    }
+
+    // Gives up on a peer that doesn't agree on a game in time, like a build
+    // from before the handshake that never says hello
+    fn check_handshake(&mut self) {
+        if !self.negotiating || self.network.is_none() {
+            self.handshake_deadline = None;
+            return;
+        }
+
+        let deadline = *self
+            .handshake_deadline
+            .get_or_insert_with(|| Instant::now() + HANDSHAKE_TIMEOUT);
+        if Instant::now() >= deadline {
+            self.handshake_deadline = None;
+            self.reject(format!(
+                "no game agreed within {} seconds, the other side may be an older version",
+                HANDSHAKE_TIMEOUT.as_secs()
+            ));
+        }
+    }
+
+    fn network_error(&mut self, text: String) {
+        println!("{}", text);
+
+        self.network = None;
+        self.netevents.clear();
+        self.clock = None;
+        self.negotiating = false;
+        self.session = None;
+
+        self.action(Action::VictoryRoyale(text));
+        self.action(Action::ClosePopups);
+    }
+
+    // Keeps the game for RECONNECT_GRACE while the host waits for the client
+    // to come back and the client tries to reach the host again
+    fn lost_connection(&mut self) {
+        let host = self.network.as_ref().is_some_and(|net| net.host);
+        let addr = if host {
+            HOST_ADDR.to_owned()
+        } else {
+            self.ip.clone()
+        };
+
+        self.network = None;
+        self.stop_clock();
+        self.negotiating = true;
+        self.flag_claimed = false;
+
+        let session = self.session.unwrap_or(0);
+        match Reconnect::new(addr, host, session, RECONNECT_GRACE) {
+            Ok(reconnect) => {
+                println!(
+                    "connection lost, waiting {} seconds for it to come back",
+                    RECONNECT_GRACE.as_secs()
+                );
+                self.reconnecting = Some(reconnect);
+            }
+            Err(e) => {
+                println!("couldn't wait for the connection to come back: {}", e);
+                self.session = None;
+                self.action(Action::Restart);
+            }
+        }
+    }
+
+    pub fn poll_reconnect(&mut self) {
+        let reconnect = match self.reconnecting.as_mut() {
+            Some(reconnect) => reconnect,
+            None => return,
+        };
+
+        match reconnect.poll() {
+            // The client says how it has the game, the host answers with the
+            // game as it is
+            Some((net, Some(theirs))) => {
+                self.network = Some(net);
+                self.reconnecting = None;
+                self.resume(theirs);
+            }
+            Some((net, None)) => {
+                self.network = Some(net);
+                self.reconnecting = None;
+
+                let snapshot = self.snapshot();
+                self.send(NetEvent::Resume(snapshot));
+            }
+            None if reconnect.expired() => {
+                println!("the connection didn't come back");
+
+                self.reconnecting = None;
+                self.session = None;
+                self.action(Action::Restart);
+            }
+            None => {}
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            session: self.session.unwrap_or(0),
+            moves: self.moves.clone(),
+            hash: polyglot::key(&self.board),
+            times: self.clock.as_ref().map(|clock| {
+                [
+                    clock.remaining(PieceColor::White),
+                    clock.remaining(PieceColor::Black),
+                ]
+            }),
+        }
+    }
+
+    // The host's side of a reconnect, its game is sent back whether the
+    // client had the same or not
+    fn resume(&mut self, theirs: Snapshot) {
+        let host = self.network.as_ref().is_some_and(|net| net.host);
+        if !host || self.session != Some(theirs.session) {
+            self.reject("there's no such game to resume".to_owned());
+            return;
+        }
+
+        let ours = self.snapshot();
+        if theirs.hash != ours.hash || theirs.moves != ours.moves {
+            println!(
+                "the client had {} moves instead of {}, sending it ours",
+                theirs.moves.len(),
+                ours.moves.len()
+            );
+        }
+
+        self.send(NetEvent::Sync(ours));
+        self.negotiating = false;
+        self.start_clock();
+    }
+
+    // The client's side of a reconnect. When it disagrees with the host it
+    // replays the host's moves, which have to lead to the host's position
+    fn resync(&mut self, host: Snapshot) {
+        if self.session != Some(host.session) {
+            self.reject("the host resumed a different game".to_owned());
+            return;
+        }
+
+        let ours = self.snapshot();
+        if ours.hash != host.hash || ours.moves != host.moves {
+            println!("out of step with the host, taking its game");
+
+            let mut board = match fen::parse(&self.start_fen) {
+                Some(board) => board,
+                None => {
+                    self.reject("lost the starting position".to_owned());
+                    return;
                 }
-                MoveEvent::Promotion(p1, p2, kind) => {
-                    let from = parse_index(p1);
-                    let to = parse_index(p2);
+            };
+            for mv in &host.moves {
+                if !uci::play_move(&mut board, mv) {
+                    self.reject(format!("the host's move {} isn't legal", mv));
+                    return;
+                }
+            }
+            if polyglot::key(&board) != host.hash {
+                self.reject("the host's moves don't give its position".to_owned());
+                return;
+            }
 
+            self.board = board;
+            self.moves = host.moves;
+            self.attackable = None;
+            self.selected = None;
+        }
+
+        if let (Some(clock), Some([white, black])) = (self.clock.as_mut(), host.times) {
+            clock.set_remaining(PieceColor::White, white);
+            clock.set_remaining(PieceColor::Black, black);
+        }
+
+        self.negotiating = false;
+        self.start_clock();
+    }
+
+    // Whether moving from `from` to `to` is a legal pawn move to the last rank
+    fn is_promotion(&mut self, from: (usize, usize), to: (usize, usize)) -> bool {
+        let pawn = self.board.board.board_squares[from.0][from.1]
+            .piece
+            .is_some_and(|piece| piece.piece_type == PieceType::Pawn);
+
+        pawn && (to.1 == 0 || to.1 == 7)
+            && self
+                .board
+                .get_available_moves(from)
+                .contains(&vec![to.0, to.1])
+    }
+
+    // Plays a move from the peer, declining it if it isn't legal
+    fn play_net_move(&mut self, mv: MoveEvent) {
+        match mv {
+            MoveEvent::Standard(p1, p2) => match (parse_index(p1), parse_index(p2)) {
+                (Some(from), Some(to))
                     if self
                         .board
                         .get_available_moves(from)
-                        .contains(&vec![to.0, to.1])
-                    {
-                        self.board.move_piece(from, to);
-                        self.board.promote(parse_piece(kind).unwrap());
-                    }
-                }
-                MoveEvent::KingsideCastle => {
-                    if !self.board.castle(CastlingSide::KingSide) {
-                        self.send(NetEvent::Decline);
-                    }
+                        .contains(&vec![to.0, to.1]) =>
+                {
+                    self.board.move_piece(from, to);
+                    self.record_move(from, to, None);
                 }
-                MoveEvent::QueensideCastle => {
-                    if !self.board.castle(CastlingSide::QueenSide) {
+                _ => self.send(NetEvent::Decline),
+            },
+            MoveEvent::Promotion(p1, p2, kind) => {
+                let kind = match parse_piece(kind) {
+                    Some(
+                        kind @ (PieceType::Queen
+                        | PieceType::Rook
+                        | PieceType::Bishop
+                        | PieceType::Knight),
+                    ) => kind,
+                    _ => {
                         self.send(NetEvent::Decline);
+                        return;
                     }
-                }
-                _ => {}
-            },
-            NetEvent::Checkmate => {
-                let team = match self.board.curr_player {
-                    PieceColor::White => "Black",
-                    PieceColor::Black => "White",
                 };
 
-                self.action(Action::ClosePopups);
-                self.action(Action::VictoryRoyale(format!("{} wins", team)));
+                match (parse_index(p1), parse_index(p2)) {
+                    (Some(from), Some(to)) if self.is_promotion(from, to) => {
+                        self.board.move_piece(from, to);
+                        self.board.promote(kind);
+                        self.record_move(from, to, Some(kind));
+                    }
+                    _ => self.send(NetEvent::Decline),
+                }
             }
-            NetEvent::Disconnect => {
-                self.action(Action::Restart);
+            MoveEvent::KingsideCastle => {
+                let king = self
+                    .board
+                    .board
+                    .find_piece(PieceType::King, self.board.curr_player);
+
+                if self.board.castle(CastlingSide::KingSide) {
+                    self.record_move(king, (6, king.1), None);
+                } else {
+                    self.send(NetEvent::Decline);
+                }
             }
-            NetEvent::Decline => {
-                self.action(Action::Restart);
+            MoveEvent::QueensideCastle => {
+                let king = self
+                    .board
+                    .board
+                    .find_piece(PieceType::King, self.board.curr_player);
+
+                if self.board.castle(CastlingSide::QueenSide) {
+                    self.record_move(king, (2, king.1), None);
+                } else {
+                    self.send(NetEvent::Decline);
+                }
             }
             _ => {}
         }
     }
 
     pub fn send_move(&mut self, event: MoveEvent) {
-        self.send(NetEvent::Move(event));
+        let time = match (self.network_color(), self.clock.as_ref()) {
+            (Some(color), Some(clock)) => Some(clock.remaining(color)),
+            _ => None,
+        };
+
+        self.send(NetEvent::Move(event, time));
     }
 
     pub fn send(&mut self, event: NetEvent) {
@@ -265,17 +846,411 @@ impl ChessState {
         }
     }
 
-    pub fn can_interact(&self) -> bool {
-        if let Some(net) = &self.network {
-            return match self.board.curr_player {
-                PieceColor::White => net.host,
-                PieceColor::Black => !net.host,
+    // Starts the engine on a thread of its own, its handshake can take
+    // seconds. The game waits for it in poll_engine_start
+    pub fn start_engine(&mut self, role: EngineRole) {
+        let path = self.engine_path.clone();
+        let (sender, receiver) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let engine = Engine::spawn(&path).and_then(|mut engine| {
+                if let EngineRole::Opponent(_) = role {
+                    engine.set_option("Ponder", Some("true"))?;
+                }
+                engine.new_game()?;
+                Ok(engine)
+            });
+
+            // Nobody is waiting any more if the game was restarted
+            let _ = sender.send(engine);
+        });
+
+        self.starting_engine = Some((role, receiver));
+    }
+
+    // Takes the engine once it's ready and starts the game. Without one the
+    // game goes on as if none was asked for
+    fn poll_engine_start(&mut self) {
+        let engine = match &self.starting_engine {
+            Some((_, receiver)) => match receiver.try_recv() {
+                Ok(engine) => engine,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    Err(io::Error::other("the engine thread stopped"))
+                }
+            },
+            None => return,
+        };
+        let role = match self.starting_engine.take() {
+            Some((role, _)) => role,
+            None => return,
+        };
+
+        match engine {
+            Ok(engine) => {
+                println!("started engine {}", engine.name);
+
+                self.review_engine = Some(self.engine_path.clone());
+                self.engine = Some(engine);
+                self.engine_role = Some(role);
+                self.searched = None;
+                self.pondering = None;
+            }
+            Err(e) => println!("couldn't start engine {}: {}", self.engine_path, e),
+        }
+
+        self.start_clock();
+    }
+
+    pub fn poll_engine(&mut self) {
+        if let Some(engine) = self.engine.as_mut() {
+            self.engineevents.append(&mut engine.read());
+        }
+    }
+
+    pub fn handle_engine_event(&mut self, ctx: &mut Context, e: EngineEvent) {
+        match e {
+            EngineEvent::Info(info) => {
+                if self.engine_role == Some(EngineRole::Analysis)
+                    && info.score.is_some()
+                    && info.multipv.unwrap_or(1) == 1
+                {
+                    let best =
+                        |info: &Option<Info>| info.as_ref().and_then(|i| i.pv.first().copied());
+                    let changed = best(&self.analysis) != info.pv.first().copied();
+
+                    self.analysis = Some(info);
+                    self.show_analysis(ctx);
+
+                    if changed {
+                        self.update_backgrounds(ctx);
+                    }
+                }
+            }
+            EngineEvent::BestMove {
+                best: Some(mv),
+                ponder,
+            } => {
+                if let Some(EngineRole::Opponent(_)) = self.engine_role {
+                    if uci::play_move(&mut self.board, &mv) {
+                        self.moves.push(mv);
+                        self.press_clock();
+                        self.announce_result();
+                        self.ponder(ponder);
+                    } else {
+                        println!("engine played illegal move {}", mv);
+                    }
+
+                    self.update_backgrounds(ctx);
+                }
+            }
+            EngineEvent::BestMove { best: None, .. } => {}
+            EngineEvent::Exited => {
+                println!("engine exited");
+
+                self.engine = None;
+                self.engine_role = None;
+                self.pondering = None;
+                self.analysis = None;
+                self.show_analysis(ctx);
+            }
+        }
+    }
+
+    // Starts a search when the position has changed since the last one and
+    // the engine has something to do in it. Returns true if it did
+    pub fn engine_think(&mut self) -> bool {
+        let plies = self.moves.len();
+        let move_limit = self.move_limit();
+        let (engine, role) = match (self.engine.as_mut(), self.engine_role) {
+            (Some(engine), Some(role)) => (engine, role),
+            _ => return false,
+        };
+
+        if self.board.promotable.is_some() || self.searched == Some(plies) {
+            return false;
+        }
+
+        let limit = match role {
+            EngineRole::Opponent(color) if color == self.board.curr_player => move_limit,
+            EngineRole::Opponent(_) => return false,
+            EngineRole::Analysis => Limit::Infinite,
+        };
+
+        let moves = &self.moves;
+        let result = engine
+            .stop()
+            .and_then(|_| engine.position(moves))
+            .and_then(|_| engine.go(limit));
+
+        if let Err(e) = result {
+            println!("couldn't talk to engine: {}", e);
+        }
+
+        self.searched = Some(plies);
+        self.analysis = None;
+        true
+    }
+
+    // Has the engine think on the move it expects from the player while the
+    // player is thinking
+    fn ponder(&mut self, expected: Option<UciMove>) {
+        let move_limit = self.move_limit();
+        let (engine, expected) = match (self.engine.as_mut(), expected) {
+            (Some(engine), Some(expected)) => (engine, expected),
+            _ => return,
+        };
+
+        // Nothing to ponder on once the game is over
+        if !uci::play_move(&mut self.board.clone(), &expected) {
+            return;
+        }
+
+        let mut moves = self.moves.clone();
+        moves.push(expected);
+
+        let result = engine
+            .position(&moves)
+            .and_then(|_| engine.go_ponder(move_limit));
+
+        match result {
+            Ok(()) => self.pondering = Some(expected),
+            Err(e) => println!("couldn't talk to engine: {}", e),
+        }
+    }
+
+    // The player has moved while the engine was pondering. Its search carries
+    // on if it guessed the move and is stopped otherwise, so engine_think
+    // starts one for the actual position
+    fn answer_ponder(&mut self) {
+        let expected = match self.pondering.take() {
+            Some(expected) => expected,
+            None => return,
+        };
+
+        let hit = self.moves.last() == Some(&expected);
+        if hit {
+            self.searched = Some(self.moves.len());
+        }
+
+        let engine = match self.engine.as_mut() {
+            Some(engine) => engine,
+            None => return,
+        };
+
+        let result = if hit {
+            engine.ponderhit()
+        } else {
+            engine.stop()
+        };
+        if let Err(e) = result {
+            println!("couldn't talk to engine: {}", e);
+        }
+    }
+
+    // Updates the analysis line and the evaluation bar, which shows white's
+    // winning chances as the white part of the bar
+    fn show_analysis(&mut self, ctx: &mut Context) {
+        let sign = if self.board.curr_player == PieceColor::White {
+            1
+        } else {
+            -1
+        };
+        let (text, eval) = match &self.analysis {
+            Some(info) => (
+                describe_info(info, self.board.curr_player),
+                info.score
+                    .map_or(0, |score| sign * review::centipawns(score)),
+            ),
+            None if self.reconnecting.is_some() => {
+                ("Connection lost, waiting for it to come back".to_owned(), 0)
+            }
+            None => ("".to_owned(), 0),
+        };
+
+        TextBlock::get(ctx.child("analysis")).set_text(text);
+
+        let mut bar = ctx.child("evalwhite");
+        let mut constraint = *bar.get::<Constraint>("constraint");
+        constraint.set_height(board_height as f64 * review::win_chance(eval) / 100.0);
+        bar.set("constraint", constraint);
+    }
+
+    // How long the engine may think: the times on the clock in a timed game,
+    // a fixed time for every move otherwise
+    fn move_limit(&self) -> Limit {
+        let clock = match &self.clock {
+            Some(clock) => clock,
+            None => return Limit::MoveTime(ENGINE_MOVE_TIME),
+        };
+
+        let millis = |time: Duration| time.as_millis() as u64;
+        let increment = |color| match clock.stage(color).timing {
+            Timing::Fischer(bonus) => millis(bonus),
+            _ => 0,
+        };
+
+        Limit::Clock {
+            wtime: millis(clock.remaining(PieceColor::White)),
+            btime: millis(clock.remaining(PieceColor::Black)),
+            winc: increment(PieceColor::White),
+            binc: increment(PieceColor::Black),
+        }
+    }
+
+    fn start_clock(&mut self) {
+        if let Some(clock) = self.clock.as_mut() {
+            clock.start(self.board.curr_player);
+        }
+    }
+
+    // Moves from the peer take its time for the move, as far as they can be
+    // trusted
+    fn press_clock(&mut self) {
+        let peer_time = self.peer_time.take();
+
+        if let Some(clock) = self.clock.as_mut() {
+            match peer_time {
+                Some(time) => clock.press_remote(time, MAX_LAG),
+                None => clock.press(),
             };
         }
+    }
+
+    fn stop_clock(&mut self) {
+        if let Some(clock) = self.clock.as_mut() {
+            clock.pause();
+        }
+    }
+
+    // Ends the game when a side has run out of time. In a network game only
+    // the side whose time it is can tell for sure, the other side claims the
+    // flag and waits for the answer
+    fn check_flag(&mut self) {
+        let flagged = match self.clock.as_ref().and_then(|c| c.flagged()) {
+            Some(flagged) => flagged,
+            None => return,
+        };
+
+        if let Some(color) = self.network_color() {
+            if flagged != color {
+                if !self.flag_claimed {
+                    self.flag_claimed = true;
+                    self.send(NetEvent::ClaimFlag);
+                }
+                return;
+            }
+
+            self.send(NetEvent::FlagFell);
+        }
+
+        self.end_on_time(flagged);
+    }
+
+    // The clock is dropped so this only happens once, its displays keep the
+    // final times
+    fn end_on_time(&mut self, flagged: PieceColor) {
+        self.clock = None;
+        self.session = None;
+
+        let result = if self.board.insufficient_material(flagged.other()) {
+            Outcome::Draw
+        } else if flagged == PieceColor::White {
+            Outcome::BlackWins
+        } else {
+            Outcome::WhiteWins
+        };
+        let (text, result) = match result {
+            Outcome::WhiteWins => ("White wins on time", "1-0"),
+            Outcome::BlackWins => ("Black wins on time", "0-1"),
+            Outcome::Draw => ("Draw, time ran out but there's no mate", "1/2-1/2"),
+        };
+        self.review_game(result);
+
+        // The engine mustn't move after the game is over
+        if let Some(engine) = self.engine.as_mut() {
+            if let Err(e) = engine.stop() {
+                println!("couldn't talk to engine: {}", e);
+            }
+        }
+        self.engine_role = None;
+        self.pondering = None;
+
+        // Actions run last in first out, so any open popup is closed first
+        self.action(Action::VictoryRoyale(text.to_owned()));
+        self.action(Action::ClosePopups);
+    }
+
+    fn show_clocks(&mut self, ctx: &mut Context) {
+        let text = match &self.clock {
+            Some(clock) => [
+                describe_clock(clock, PieceColor::White),
+                describe_clock(clock, PieceColor::Black),
+            ],
+            None => return,
+        };
+
+        self.set_clock_text(ctx, text);
+    }
+
+    // Only touches the displays that changed, this runs every frame
+    fn set_clock_text(&mut self, ctx: &mut Context, text: [String; 2]) {
+        for (i, (id, text)) in ["clockwhite", "clockblack"]
+            .iter()
+            .zip(text.iter())
+            .enumerate()
+        {
+            if self.clock_text[i] != *text {
+                TextBlock::get(ctx.child(*id)).set_text(text.clone());
+                self.clock_text[i] = text.clone();
+            }
+        }
+    }
+
+    fn best_move(&self) -> Option<UciMove> {
+        self.analysis.as_ref()?.pv.first().copied()
+    }
+
+    fn record_move(
+        &mut self,
+        from: (usize, usize),
+        to: (usize, usize),
+        promotion: Option<PieceType>,
+    ) {
+        self.moves.push(UciMove {
+            from,
+            to,
+            promotion,
+        });
+        self.press_clock();
+        self.answer_ponder();
+    }
+
+    pub fn can_interact(&self) -> bool {
+        if self.starting_engine.is_some() {
+            return false;
+        }
+
+        if let (Some(_), Some(EngineRole::Opponent(color))) = (&self.engine, self.engine_role) {
+            return color != self.board.curr_player;
+        }
+
+        if self.negotiating {
+            return false;
+        }
+
+        if let Some(color) = self.network_color() {
+            return color == self.board.curr_player;
+        }
 
         true
     }
 
+    // The side this end of a network game plays
+    fn network_color(&self) -> Option<PieceColor> {
+        self.network.as_ref().and(self.color)
+    }
+
     pub fn press_tile(&mut self, ctx: &mut Context, point: (usize, usize)) {
         if !self.can_interact() {
             return;
@@ -302,33 +1277,99 @@ impl ChessState {
                 if self.board.promotable.is_some() {
                     self.action(Action::ShowPromotion());
                 } else {
+                    self.record_move(self.selected.unwrap(), point, None);
                     self.send_move(MoveEvent::Standard(
                         encode_index(self.selected.unwrap()),
                         encode_index(point),
                     ));
                 }
 
-                let (checkmate, stalemate) = self.board.check_for_win();
+                self.announce_result();
+            }
 
-                if checkmate {
-                    let team = match self.board.curr_player {
-                        PieceColor::White => "Black",
-                        PieceColor::Black => "White",
-                    };
+            self.attackable = None;
+            self.selected = None;
+        }
+    }
 
-                    self.send(NetEvent::Checkmate);
-                    self.action(Action::VictoryRoyale(format!("{} wins", team)));
-                }
+    fn announce_result(&mut self) {
+        let (checkmate, stalemate) = self.board.check_for_win();
 
-                if stalemate {
-                    self.send(NetEvent::Draw);
-                    self.action(Action::VictoryRoyale("Stalemate :(".to_owned()));
-                }
+        if checkmate || stalemate {
+            self.stop_clock();
+            self.session = None;
+        }
+
+        if checkmate {
+            let team = match self.board.curr_player {
+                PieceColor::White => "Black",
+                PieceColor::Black => "White",
+            };
+
+            self.send(NetEvent::Checkmate);
+            self.action(Action::VictoryRoyale(format!("{} wins", team)));
+            self.review_game(if team == "White" { "1-0" } else { "0-1" });
+        }
+
+        if stalemate {
+            self.send(NetEvent::Draw);
+            self.action(Action::VictoryRoyale("Stalemate :(".to_owned()));
+            self.review_game("1/2-1/2");
+        }
+    }
+
+    // Has the engine go through the finished game in the background and
+    // writes the annotated game to a file of its own. Nothing happens unless
+    // an engine has been set
+    fn review_game(&self, result: &'static str) {
+        let path = match &self.review_engine {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        let moves = self.moves.clone();
+        let [white, black] = self.player_names();
+
+        // Named after when the game ended, so earlier reviews are kept
+        let ended = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let file = format!("review-{}.pgn", ended);
+
+        std::thread::spawn(move || {
+            let review = Engine::spawn(&path).and_then(|mut engine| {
+                review::review(&mut engine, &moves, Limit::MoveTime(REVIEW_MOVE_TIME))
+            });
+
+            let tags = vec![
+                ("Event".to_owned(), "Casual game".to_owned()),
+                ("White".to_owned(), white),
+                ("Black".to_owned(), black),
+                ("Result".to_owned(), result.to_owned()),
+            ];
+
+            match review.and_then(|review| std::fs::write(&file, review.to_pgn(&tags, result))) {
+                Ok(()) => println!("wrote game review to {}", file),
+                Err(e) => println!("couldn't review game with {}: {}", path, e),
             }
+        });
+    }
 
-            self.attackable = None;
-            self.selected = None;
+    // The White and Black tags, the engine or peer's name for their side
+    // and ? for anyone else
+    fn player_names(&self) -> [String; 2] {
+        let mut names = ["?".to_owned(), "?".to_owned()];
+
+        if let (Some(engine), Some(EngineRole::Opponent(color))) = (&self.engine, self.engine_role)
+        {
+            if !engine.name.is_empty() {
+                names[color as usize] = engine.name.clone();
+            }
         }
+        if let (Some(peer), Some(color)) = (&self.peer, self.network_color()) {
+            names[color.other() as usize] = peer.name.clone();
+        }
+
+        names
     }
 
     pub fn update_backgrounds(&mut self, ctx: &mut Context) {
@@ -341,6 +1382,11 @@ impl ChessState {
             }
         }
 
+        if let Some(best) = self.best_move() {
+            self.color_tile(ctx, best.from, BEST_MOVE_TILES);
+            self.color_tile(ctx, best.to, BEST_MOVE_TILES);
+        }
+
         if let Some(att) = self.attackable.as_ref() {
             for vec in att {
                 let point = (vec[0], vec[1]);
@@ -388,6 +1434,49 @@ widget!(
     }
 );
 
+// One line summary of an analysis, with the score from white's side
+fn describe_info(info: &Info, to_move: PieceColor) -> String {
+    let sign = if to_move == PieceColor::White { 1 } else { -1 };
+    let score = match info.score {
+        Some(Score::Centipawns(cp)) => format!("{:+.2}", (sign * cp) as f64 / 100.0),
+        Some(Score::Mate(moves)) => format!("#{}", sign * moves),
+        None => "".to_owned(),
+    };
+    let pv: Vec<String> = info.pv.iter().map(|mv| mv.to_string()).collect();
+
+    format!(
+        "depth {}  {}  {}",
+        info.depth.unwrap_or(0),
+        score,
+        pv.join(" ")
+    )
+}
+
+// The side and its time left, in tenths of a second once under ten seconds
+fn describe_clock(clock: &Clock, color: PieceColor) -> String {
+    let name = match color {
+        PieceColor::White => "White",
+        PieceColor::Black => "Black",
+    };
+    let tenths = clock.remaining(color).as_millis() / 100;
+    let seconds = tenths / 10;
+
+    let time = if seconds < 10 {
+        format!("{}.{}", seconds, tenths % 10)
+    } else if seconds < 3600 {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    } else {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    };
+
+    format!("{} {}", name, time)
+}
+
 fn get_id((x, y): (usize, usize)) -> String {
     format!("{}{}", x, y)
 }
@@ -440,7 +1529,50 @@ impl Template for ChessBoard {
             }
         }
 
-        self.name("ChessGrid").child(grid.build(ctx))
+        let analysis = TextBlock::new()
+            .id("analysis")
+            .height(ANALYSIS_HEIGHT as f64)
+            .margin((8, 4, 8, 0))
+            .build(ctx);
+
+        let evalbar = Container::new()
+            .background("#303030")
+            .width(EVAL_BAR_WIDTH as f64)
+            .height(board_height as f64)
+            .child(
+                Container::new()
+                    .id("evalwhite")
+                    .background("#f0f0f0")
+                    .v_align("end")
+                    .height(board_height as f64 / 2.0)
+                    .build(ctx),
+            )
+            .build(ctx);
+
+        // The first rank is drawn at the top, so white's clock goes there
+        let clock = |id: &str, ctx: &mut BuildContext| {
+            TextBlock::new()
+                .id(id)
+                .height(CLOCK_HEIGHT as f64)
+                .font_size(24)
+                .margin((EVAL_BAR_WIDTH + 8, 4, 8, 0))
+                .build(ctx)
+        };
+
+        self.name("ChessGrid").child(
+            Stack::new()
+                .child(clock("clockwhite", ctx))
+                .child(
+                    Stack::new()
+                        .orientation("horizontal")
+                        .child(evalbar)
+                        .child(grid.build(ctx))
+                        .build(ctx),
+                )
+                .child(clock("clockblack", ctx))
+                .child(analysis)
+                .build(ctx),
+        )
     }
 }
 
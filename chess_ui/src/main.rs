@@ -10,12 +10,18 @@ use chess_widget::ChessBoard;
 
 pub const board_width: i32 = 512;
 pub const board_height: i32 = 512;
+pub const ANALYSIS_HEIGHT: i32 = 24;
+pub const EVAL_BAR_WIDTH: i32 = 24;
+pub const CLOCK_HEIGHT: i32 = 32;
 fn main() {
     Application::new()
         .window(move |ctx| {
             Window::new()
                 .title("Chess")
-                .size(board_height, board_height)
+                .size(
+                    board_width + EVAL_BAR_WIDTH,
+                    board_height + ANALYSIS_HEIGHT + 2 * CLOCK_HEIGHT,
+                )
                 .child(ChessBoard::new().build(ctx))
                 .build(ctx)
         })
@@ -1,13 +1,38 @@
+use std::collections::hash_map::RandomState;
 use std::collections::VecDeque;
+use std::hash::{BuildHasher, Hasher};
 use std::io::{Read, Result, Write};
-use std::net::{TcpListener, TcpStream};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
 use std::sync::mpsc;
-use std::thread::{spawn, JoinHandle};
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use crate::PieceType;
-
-type Buffer = [u8; 32];
+use chess_engine::piece::Color as PieceColor;
+use chess_engine::uci::{self, UciMove};
+
+// How often a client tries to get a lost connection back, and how long it
+// waits each time
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(200);
+
+// Peers only play each other with the same version
+pub const PROTOCOL_VERSION: u16 = 2;
+pub const CLIENT_NAME: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+// What this build can play beyond a standard game from the start position
+pub const FEATURES: &[&str] = &[FEATURE_CLOCK, FEATURE_FEN];
+pub const FEATURE_CLOCK: &str = "clock";
+pub const FEATURE_FEN: &str = "fen";
+pub const VARIANTS: &[&str] = &["standard"];
+
+// Sent in place of the time left in a game without clocks
+const UNTIMED: u32 = u32::MAX;
+
+// TCP can split or join writes, so every message is sent as its length, two
+// bytes big endian, and then its bytes
+pub const MAX_FRAME: usize = u16::MAX as usize;
+// How much is read from the stream at a time, messages can be any length
+const READ_SIZE: usize = 1024;
 
 #[derive(Copy, Clone, Debug)]
 pub enum MoveEvent {
@@ -19,22 +44,221 @@ pub enum MoveEvent {
     Other,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub enum NetEvent {
     Decline,
-    Move(MoveEvent),
+    // With the mover's time left after it, None in an untimed game
+    Move(MoveEvent, Option<Duration>),
     Undo,
     Accept,
     Checkmate,
     Draw,
     Resign,
+    // Sent by both sides as soon as they're connected
+    Hello(Hello),
+    // The game the host proposes, the client answers with Accept or Reject
+    Setup(Setup),
+    // Why the sender won't play, it hangs up after sending it
+    Reject(String),
+    // The other side's time has run out on the sender's clock
+    ClaimFlag,
+    // The sender's own time has run out
+    FlagFell,
+    // The sender's time left, the answer to a claim that was wrong
+    Time(Duration),
+    // Sent by a client that has reconnected, with the game as it has it
+    Resume(Snapshot),
+    // The host's answer to Resume, its game is the one played on
+    Sync(Snapshot),
     Disconnect,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hello {
+    pub version: u16,
+    pub name: String,
+    pub features: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Setup {
+    // The colour the host plays
+    pub host_color: PieceColor,
+    pub variant: String,
+    pub fen: String,
+    // In the form clock::TimeControl reads, empty for an untimed game
+    pub time_control: String,
+    // Names the game when the client reconnects
+    pub session: u64,
+}
+
+// Where a game stands, for both sides to check they agree after reconnecting
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub session: u64,
+    // From the agreed starting position
+    pub moves: Vec<UciMove>,
+    // polyglot::key of the position after the moves
+    pub hash: u64,
+    // White's and black's time left in a timed game
+    pub times: Option<[Duration; 2]>,
+}
+
+// Gets a lost connection back before `deadline`, the host by listening for
+// the client again and the client by dialling the host again
+pub struct Reconnect {
+    pub deadline: Instant,
+    addr: String,
+    listener: Option<TcpListener>,
+    // The game the host is keeping, only a client resuming it is let back in
+    session: u64,
+    // Connections to the host that haven't said which game they resume yet
+    pending: Vec<ChessNet>,
+    next_try: Instant,
+}
+
 pub struct ChessNet {
     pub host: bool,
     stream: TcpStream,
-    read_channel: mpsc::Receiver<NetEvent>,
+    reader: Reader<NetEvent>,
+}
+
+// Puts messages back together from the bytes as they come in
+#[derive(Clone, Debug, Default)]
+pub struct Decoder {
+    buffer: Vec<u8>,
+}
+
+// Reads messages from a stream on a thread of its own and queues them up
+pub struct Reader<T> {
+    channel: mpsc::Receiver<T>,
+}
+
+impl Hello {
+    pub fn ours() -> Hello {
+        Hello {
+            version: PROTOCOL_VERSION,
+            name: CLIENT_NAME.to_owned(),
+            features: FEATURES.iter().map(|f| (*f).to_owned()).collect(),
+        }
+    }
+}
+
+impl Setup {
+    // Features the other side needs to play this game
+    pub fn required_features(&self) -> Vec<&'static str> {
+        let mut features = Vec::new();
+        if !self.time_control.is_empty() {
+            features.push(FEATURE_CLOCK);
+        }
+        if self.fen != chess_engine::fen::START {
+            features.push(FEATURE_FEN);
+        }
+
+        features
+    }
+}
+
+impl Reconnect {
+    pub fn new(addr: String, host: bool, session: u64, grace: Duration) -> Result<Reconnect> {
+        let listener = if host {
+            let listener = TcpListener::bind(&addr)?;
+            listener.set_nonblocking(true)?;
+            Some(listener)
+        } else {
+            None
+        };
+
+        Ok(Reconnect {
+            deadline: Instant::now() + grace,
+            addr,
+            listener,
+            session,
+            pending: Vec::new(),
+            next_try: Instant::now(),
+        })
+    }
+
+    // The new connection once there is one, and for the host the game the
+    // client has. Never blocks for longer than CONNECT_TIMEOUT, this is
+    // called every frame
+    pub fn poll(&mut self) -> Option<(ChessNet, Option<Snapshot>)> {
+        if let Some(listener) = &self.listener {
+            if let Ok((client, addr)) = listener.accept() {
+                if client.set_nonblocking(false).is_ok() {
+                    println!("connection from {}, waiting for it to resume", addr);
+                    self.pending.push(ChessNet::new(client, true));
+                }
+            }
+
+            return self.resumed();
+        }
+
+        if Instant::now() < self.next_try {
+            return None;
+        }
+        self.next_try = Instant::now() + RETRY_INTERVAL;
+
+        let addr = self.addr.to_socket_addrs().ok()?.next()?;
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).ok()?;
+        Some((ChessNet::new(stream, false), None))
+    }
+
+    // The first pending connection to resume the host's game. The ones that
+    // ask for another game or hang up are dropped, the game waits on
+    fn resumed(&mut self) -> Option<(ChessNet, Option<Snapshot>)> {
+        let mut i = 0;
+
+        while i < self.pending.len() {
+            let net = &mut self.pending[i];
+            let mut keep = true;
+
+            for event in net.read() {
+                match event {
+                    NetEvent::Resume(theirs) if theirs.session == self.session => {
+                        println!("client is back!");
+                        return Some((self.pending.remove(i), Some(theirs)));
+                    }
+                    NetEvent::Resume(_) => {
+                        println!("dropping a connection resuming another game");
+                        net.send(NetEvent::Reject(
+                            "there's no such game to resume".to_owned(),
+                        ));
+                        keep = false;
+                        break;
+                    }
+                    NetEvent::Disconnect => {
+                        keep = false;
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+
+            if keep {
+                i += 1;
+            } else {
+                self.pending.remove(i);
+            }
+        }
+
+        None
+    }
+
+    pub fn expired(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
+}
+
+// Hard to guess, so a stray connection can't take over a game
+pub fn new_session_id() -> u64 {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(since_epoch.as_nanos());
+    hasher.finish()
 }
 
 impl ChessNet {
@@ -57,82 +281,319 @@ impl ChessNet {
     }
 
     pub fn new(stream: TcpStream, host: bool) -> Self {
-        let (w_reader, r_reader) = mpsc::channel::<NetEvent>();
-        let mut rstream = stream.try_clone().ok().unwrap();
-
-        spawn(move || {
-            let mut buf = [0; 32];
-            while let Ok(read) = rstream.read(&mut buf) {
-                println!("recieved message, len {}", read);
-                if read == 0 {
-                    break;
+        let rstream = stream.try_clone().ok().unwrap();
+        let reader = Reader::spawn(
+            rstream,
+            |frame| {
+                println!("recieved message, len {}", frame.len());
+
+                let event = parse_incoming(frame);
+                if event.is_none() {
+                    println!("ignoring unreadable message {:?}", frame);
                 }
+                event
+            },
+            NetEvent::Disconnect,
+        );
 
-                w_reader
-                    .send(parse_incoming(&buf))
-                    .expect("couldn't write to channel");
-            }
-
-            w_reader
-                .send(NetEvent::Disconnect)
-                .expect("couldn't write to channel");
-        });
-
-        Self {
+        let mut net = Self {
             stream: stream,
-            read_channel: r_reader,
+            reader: reader,
             host: host,
-        }
+        };
+        net.send(NetEvent::Hello(Hello::ours()));
+
+        net
     }
 
     pub fn send(&mut self, event: NetEvent) {
-        self.stream
-            .write(&encode_event(event))
-            .expect("failed write");
+        let frame = match encode_frame(&encode_event(event)) {
+            Some(frame) => frame,
+            None => {
+                println!("couldn't send message, it's too long");
+                return;
+            }
+        };
+
+        // A broken connection shows up as a Disconnect from the reader
+        if let Err(e) = self.stream.write_all(&frame) {
+            println!("couldn't send message: {}", e);
+        }
     }
 
+    // Events in the order they were sent
     pub fn read(&mut self) -> VecDeque<NetEvent> {
-        let mut queue = VecDeque::<NetEvent>::new();
+        let queue = self.reader.read();
 
-        while let Ok(read) = self.read_channel.try_recv() {
+        for read in &queue {
             println!("sucessfully read {:?}", read);
-
-            queue.push_front(read);
         }
 
         queue
     }
 }
 
-fn parse_incoming(buffer: &Buffer) -> NetEvent {
-    let current = buffer[0];
+// The reader thread has its own handle on the stream, so it's shut down to
+// hang up
+impl Drop for ChessNet {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+// The bytes to send for `message`, None if it's longer than MAX_FRAME
+pub fn encode_frame(message: &[u8]) -> Option<Vec<u8>> {
+    if message.len() > MAX_FRAME {
+        return None;
+    }
+
+    let mut frame = Vec::with_capacity(message.len() + 2);
+    frame.extend_from_slice(&(message.len() as u16).to_be_bytes());
+    frame.extend_from_slice(message);
+    Some(frame)
+}
+
+impl Decoder {
+    pub fn new() -> Decoder {
+        Decoder::default()
+    }
+
+    // Adds bytes read from the stream, any number at a time
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    // The next whole message, None until all of it has arrived
+    pub fn next_frame(&mut self) -> Option<Vec<u8>> {
+        if self.buffer.len() < 2 {
+            return None;
+        }
 
-    match current {
+        let len = u16::from_be_bytes([self.buffer[0], self.buffer[1]]) as usize;
+        if self.buffer.len() < 2 + len {
+            return None;
+        }
+
+        let frame = self.buffer[2..2 + len].to_vec();
+        self.buffer.drain(..2 + len);
+        Some(frame)
+    }
+}
+
+impl<T: Send + 'static> Reader<T> {
+    // Every message is turned into a T by `parse`, the ones it gives None for
+    // are skipped. `closed` is queued last, once the stream has ended
+    pub fn spawn<R, F>(mut stream: R, parse: F, closed: T) -> Reader<T>
+    where
+        R: Read + Send + 'static,
+        F: Fn(&[u8]) -> Option<T> + Send + 'static,
+    {
+        let (sender, channel) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut buf = [0; READ_SIZE];
+            let mut decoder = Decoder::new();
+
+            while let Ok(read) = stream.read(&mut buf) {
+                if read == 0 {
+                    break;
+                }
+
+                decoder.extend(&buf[..read]);
+                while let Some(frame) = decoder.next_frame() {
+                    if let Some(message) = parse(&frame) {
+                        // Nobody is listening any more if the Reader was
+                        // dropped
+                        if sender.send(message).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+
+            let _ = sender.send(closed);
+        });
+
+        Reader { channel }
+    }
+
+    // Everything that has arrived since the last call, oldest first
+    pub fn read(&self) -> VecDeque<T> {
+        self.channel.try_iter().collect()
+    }
+}
+
+// Reads one message, None if it's malformed or from a newer version
+fn parse_incoming(frame: &[u8]) -> Option<NetEvent> {
+    let (&current, rest) = frame.split_first()?;
+
+    Some(match current {
         0 => NetEvent::Decline,
         1 => {
-            let current = buffer[1];
-            NetEvent::Move(match current {
-                0 => MoveEvent::Standard(buffer[2], buffer[3]),
-                1 => MoveEvent::Standard(buffer[2], buffer[3]),
-                2 => MoveEvent::Promotion(buffer[2], buffer[3], buffer[4]),
-                3 => MoveEvent::KingsideCastle,
-                4 => MoveEvent::QueensideCastle,
+            let time = match parse_millis(rest)? {
+                UNTIMED => None,
+                millis => Some(Duration::from_millis(millis as u64)),
+            };
+
+            let mv = match &rest[4..] {
+                [0, p1, p2] => MoveEvent::Standard(*p1, *p2),
+                [1, p1, p2] => MoveEvent::Standard(*p1, *p2),
+                [2, p1, p2, kind] => MoveEvent::Promotion(*p1, *p2, *kind),
+                [3] => MoveEvent::KingsideCastle,
+                [4] => MoveEvent::QueensideCastle,
                 _ => MoveEvent::Other,
-            })
+            };
+
+            NetEvent::Move(mv, time)
         }
         2 => NetEvent::Undo,
         3 => NetEvent::Accept,
         4 => NetEvent::Checkmate,
         5 => NetEvent::Draw,
         6 => NetEvent::Resign,
-        _ => NetEvent::Disconnect,
+        7 => NetEvent::Reject(String::from_utf8(rest.to_vec()).ok()?),
+        8 => NetEvent::ClaimFlag,
+        9 => NetEvent::FlagFell,
+        10 => NetEvent::Time(Duration::from_millis(parse_millis(rest)? as u64)),
+        11 => {
+            let mut rest = rest;
+            // Older and newer versions keep this first so mismatches can be told
+            let version = match rest {
+                [a, b, ..] => u16::from_be_bytes([*a, *b]),
+                _ => return None,
+            };
+            rest = &rest[2..];
+            let name = take_text(&mut rest)?;
+            let (&count, mut rest) = rest.split_first()?;
+
+            let mut features = Vec::new();
+            for _ in 0..count {
+                features.push(take_text(&mut rest)?);
+            }
+
+            NetEvent::Hello(Hello {
+                version,
+                name,
+                features,
+            })
+        }
+        12 => {
+            let (&color, mut rest) = rest.split_first()?;
+
+            NetEvent::Setup(Setup {
+                host_color: match color {
+                    0 => PieceColor::White,
+                    1 => PieceColor::Black,
+                    _ => return None,
+                },
+                variant: take_text(&mut rest)?,
+                fen: take_text(&mut rest)?,
+                time_control: take_text(&mut rest)?,
+                session: take_u64(&mut rest)?,
+            })
+        }
+        13 => NetEvent::Resume(parse_snapshot(rest)?),
+        14 => NetEvent::Sync(parse_snapshot(rest)?),
+        _ => return None,
+    })
+}
+
+fn parse_snapshot(mut bytes: &[u8]) -> Option<Snapshot> {
+    let session = take_u64(&mut bytes)?;
+    let hash = take_u64(&mut bytes)?;
+    let (&timed, mut bytes) = bytes.split_first()?;
+
+    let times = if timed == 1 {
+        let white = parse_millis(bytes)?;
+        let black = parse_millis(bytes.get(4..)?)?;
+        bytes = &bytes[8..];
+
+        Some([
+            Duration::from_millis(white as u64),
+            Duration::from_millis(black as u64),
+        ])
+    } else {
+        None
+    };
+
+    let moves = take_text(&mut bytes)?
+        .split_whitespace()
+        .map(uci::parse_move)
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(Snapshot {
+        session,
+        moves,
+        hash,
+        times,
+    })
+}
+
+fn push_snapshot(ret: &mut Vec<u8>, snapshot: &Snapshot) {
+    ret.extend_from_slice(&snapshot.session.to_be_bytes());
+    ret.extend_from_slice(&snapshot.hash.to_be_bytes());
+
+    match snapshot.times {
+        Some([white, black]) => {
+            ret.push(1);
+            ret.extend_from_slice(&encode_millis(white));
+            ret.extend_from_slice(&encode_millis(black));
+        }
+        None => ret.push(0),
     }
+
+    let moves: Vec<String> = snapshot.moves.iter().map(|mv| mv.to_string()).collect();
+    push_text(ret, &moves.join(" "));
+}
+
+fn take_u64(bytes: &mut &[u8]) -> Option<u64> {
+    let value = match bytes {
+        [a, b, c, d, e, f, g, h, ..] => u64::from_be_bytes([*a, *b, *c, *d, *e, *f, *g, *h]),
+        _ => return None,
+    };
+
+    *bytes = &bytes[8..];
+    Some(value)
+}
+
+// Reads a string written by push_text off the front of `bytes`
+fn take_text(bytes: &mut &[u8]) -> Option<String> {
+    let len = match bytes {
+        [a, b, ..] => u16::from_be_bytes([*a, *b]) as usize,
+        _ => return None,
+    };
+    let text = String::from_utf8(bytes.get(2..2 + len)?.to_vec()).ok()?;
+
+    *bytes = &bytes[2 + len..];
+    Some(text)
+}
+
+fn push_text(ret: &mut Vec<u8>, text: &str) {
+    let bytes = &text.as_bytes()[..text.len().min(u16::MAX as usize)];
+
+    ret.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    ret.extend_from_slice(bytes);
+}
+
+fn parse_millis(bytes: &[u8]) -> Option<u32> {
+    match bytes {
+        [a, b, c, d, ..] => Some(u32::from_be_bytes([*a, *b, *c, *d])),
+        _ => None,
+    }
+}
+
+fn encode_millis(time: Duration) -> [u8; 4] {
+    (time.as_millis().min(UNTIMED as u128 - 1) as u32).to_be_bytes()
 }
 
-pub fn parse_index(index: u8) -> (usize, usize) {
+// None for anything off the board
+pub fn parse_index(index: u8) -> Option<(usize, usize)> {
     let index = index as usize;
+    if index >= 64 {
+        return None;
+    }
 
-    (index % 8, index / 8)
+    Some((index % 8, index / 8))
 }
 
 pub fn encode_index((x, y): (usize, usize)) -> u8 {
@@ -173,8 +634,12 @@ pub fn encode_event(e: NetEvent) -> Vec<u8> {
     use NetEvent::*;
     match e {
         Decline => ret.push(0),
-        Move(mv) => {
+        Move(mv, time) => {
             ret.push(1);
+            match time {
+                Some(time) => ret.extend_from_slice(&encode_millis(time)),
+                None => ret.extend_from_slice(&UNTIMED.to_be_bytes()),
+            }
 
             use MoveEvent::*;
             match mv {
@@ -191,8 +656,196 @@ pub fn encode_event(e: NetEvent) -> Vec<u8> {
         Checkmate => ret.push(4),
         Draw => ret.push(5),
         Resign => ret.push(6),
+        Reject(reason) => {
+            ret.push(7);
+            ret.extend_from_slice(reason.as_bytes());
+        }
+        ClaimFlag => ret.push(8),
+        FlagFell => ret.push(9),
+        Time(time) => {
+            ret.push(10);
+            ret.extend_from_slice(&encode_millis(time));
+        }
+        Hello(hello) => {
+            ret.push(11);
+            ret.extend_from_slice(&hello.version.to_be_bytes());
+            push_text(&mut ret, &hello.name);
+
+            let features = &hello.features[..hello.features.len().min(u8::MAX as usize)];
+            ret.push(features.len() as u8);
+            for feature in features {
+                push_text(&mut ret, feature);
+            }
+        }
+        Setup(setup) => {
+            ret.push(12);
+            ret.push(match setup.host_color {
+                PieceColor::White => 0,
+                PieceColor::Black => 1,
+            });
+            push_text(&mut ret, &setup.variant);
+            push_text(&mut ret, &setup.fen);
+            push_text(&mut ret, &setup.time_control);
+            ret.extend_from_slice(&setup.session.to_be_bytes());
+        }
+        Resume(snapshot) => {
+            ret.push(13);
+            push_snapshot(&mut ret, &snapshot);
+        }
+        Sync(snapshot) => {
+            ret.push(14);
+            push_snapshot(&mut ret, &snapshot);
+        }
         _ => {}
     }
 
     ret
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    const ADDR: &str = "127.0.0.1:47391";
+
+    fn stream(messages: &[&[u8]]) -> Vec<u8> {
+        messages
+            .iter()
+            .flat_map(|m| encode_frame(m).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn encodes_the_length_first() {
+        assert_eq!(encode_frame(&[7, 8, 9]), Some(vec![0, 3, 7, 8, 9]));
+        assert_eq!(encode_frame(&[]), Some(vec![0, 0]));
+        assert_eq!(encode_frame(&vec![1; 300]).unwrap()[..2], [1, 44]);
+
+        assert_eq!(
+            encode_frame(&vec![1; MAX_FRAME]).unwrap().len(),
+            MAX_FRAME + 2
+        );
+        assert_eq!(encode_frame(&vec![1; MAX_FRAME + 1]), None);
+    }
+
+    #[test]
+    fn splits_joined_messages() {
+        let mut decoder = Decoder::new();
+        decoder.extend(&stream(&[&[1, 2, 3], &[], &[4]]));
+
+        assert_eq!(decoder.next_frame(), Some(vec![1, 2, 3]));
+        assert_eq!(decoder.next_frame(), Some(vec![]));
+        assert_eq!(decoder.next_frame(), Some(vec![4]));
+        assert_eq!(decoder.next_frame(), None);
+        assert_eq!(decoder.buffer.len(), 0);
+    }
+
+    #[test]
+    fn waits_for_split_messages() {
+        let long = vec![5; 1000];
+        let bytes = stream(&[&[1, 2, 3], &long, &[6]]);
+
+        // One byte at a time, including between the two length bytes
+        let mut decoder = Decoder::new();
+        let mut frames = Vec::new();
+        for byte in &bytes {
+            decoder.extend(&[*byte]);
+            while let Some(frame) = decoder.next_frame() {
+                frames.push(frame);
+            }
+        }
+        assert_eq!(frames, vec![vec![1, 2, 3], long.clone(), vec![6]]);
+
+        // Reads that end part way through one message and the next
+        let mut decoder = Decoder::new();
+        decoder.extend(&bytes[..4]);
+        assert_eq!(decoder.next_frame(), None);
+        assert_eq!(decoder.buffer.len(), 4);
+
+        decoder.extend(&bytes[4..500]);
+        assert_eq!(decoder.next_frame(), Some(vec![1, 2, 3]));
+        assert_eq!(decoder.next_frame(), None);
+
+        decoder.extend(&bytes[500..]);
+        assert_eq!(decoder.next_frame(), Some(long));
+        assert_eq!(decoder.next_frame(), Some(vec![6]));
+        assert_eq!(decoder.next_frame(), None);
+    }
+
+    #[test]
+    fn reader_queues_messages_in_order() {
+        // All of these arrive in a single read
+        let bytes = stream(&[&[1], &[2, 2], &[], &[3], &[4, 4, 4]]);
+        let parse = |frame: &[u8]| {
+            if frame.is_empty() {
+                None
+            } else {
+                Some(frame.to_vec())
+            }
+        };
+        let reader = Reader::spawn(Cursor::new(bytes), parse, vec![0]);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut messages = Vec::new();
+        while messages.last() != Some(&vec![0]) && Instant::now() < deadline {
+            messages.extend(reader.read());
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(
+            messages,
+            vec![vec![1], vec![2, 2], vec![3], vec![4, 4, 4], vec![0]]
+        );
+    }
+
+    fn resume(session: u64) -> NetEvent {
+        NetEvent::Resume(Snapshot {
+            session,
+            moves: Vec::new(),
+            hash: 0,
+            times: None,
+        })
+    }
+
+    // Polls until there's something or a few seconds have gone by
+    fn wait_for<T>(mut poll: impl FnMut() -> Option<T>) -> Option<T> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+
+        while Instant::now() < deadline {
+            if let Some(found) = poll() {
+                return Some(found);
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        None
+    }
+
+    #[test]
+    fn reconnects_only_to_the_same_game() {
+        let mut host = Reconnect::new(ADDR.to_owned(), true, 42, Duration::from_secs(30)).unwrap();
+
+        // Neither a peer that says nothing nor one resuming another game
+        // gets in or ends the wait
+        let _silent = ChessNet::connect(ADDR.to_owned()).unwrap();
+        let mut stray = ChessNet::connect(ADDR.to_owned()).unwrap();
+        stray.send(resume(7));
+
+        let rejected = wait_for(|| {
+            assert!(host.poll().is_none());
+            stray
+                .read()
+                .into_iter()
+                .find(|event| matches!(event, NetEvent::Reject(_)))
+        });
+        assert!(rejected.is_some());
+
+        let mut client = ChessNet::connect(ADDR.to_owned()).unwrap();
+        client.send(resume(42));
+
+        let (net, theirs) = wait_for(|| host.poll()).unwrap();
+        assert!(net.host);
+        assert_eq!(theirs.map(|snapshot| snapshot.session), Some(42));
+    }
+}
@@ -1,5 +1,6 @@
 use super::*;
 use crate::chess_widget::*;
+use chess_engine::piece::Color as PieceColor;
 use orbtk::prelude::*;
 
 pub fn popup_win(id: Entity, ctx: &mut BuildContext, text: String) -> Entity {
@@ -95,11 +96,26 @@ fn promote_tile(id: Entity, ctx: &mut BuildContext, text: String, kind: PieceTyp
         .build(ctx)
 }
 
-pub fn popup_start(id: Entity, ctx: &mut BuildContext, ip: String) -> (Entity, Entity) {
+pub fn popup_start(
+    id: Entity,
+    ctx: &mut BuildContext,
+    ip: String,
+    engine: String,
+    time_control: String,
+) -> (Entity, Entity, Entity, Entity) {
     let ipbox = TextBox::new().text(ip).id("ipbox").build(ctx);
+    let enginebox = TextBox::new().text(engine).id("enginebox").build(ctx);
+    let tcbox = TextBox::new()
+        .text(time_control)
+        .water_mark("time control, e.g. 300+3")
+        .id("tcbox")
+        .attach(Grid::row(0))
+        .build(ctx);
 
     (
         ipbox,
+        enginebox,
+        tcbox,
         Popup::new()
             .target(id)
             .open(true)
@@ -111,42 +127,44 @@ pub fn popup_start(id: Entity, ctx: &mut BuildContext, ip: String) -> (Entity, E
                         Grid::new()
                             .rows(
                                 Rows::create()
+                                    .push("auto")
+                                    .push(50)
+                                    .push("auto")
+                                    .push(50)
                                     .push("auto")
                                     .push(50)
                                     .push("auto")
                                     .push(50)
                                     .push("*"),
                             )
+                            .child(tcbox)
                             .child(
                                 Button::new()
                                     .text("Play Local")
                                     .on_click(move |state, _| {
                                         let cs: &mut ChessState = state.get_mut(id);
                                         cs.action(Action::ClosePopups);
+                                        cs.action(Action::StartGame);
 
                                         true
                                     })
-                                    .attach(Grid::row(0))
+                                    .attach(Grid::row(2))
                                     .build(ctx),
                             )
                             .child(
-                                Button::new()
-                                    .text("host session")
-                                    .attach(Grid::row(2))
-                                    .on_click(move |state, _| {
-                                        let cs: &mut ChessState = state.get_mut(id);
-                                        cs.action(Action::ClosePopups);
-                                        cs.host();
-
-                                        true
-                                    })
+                                Stack::new()
+                                    .h_align("center")
+                                    .spacing(5)
+                                    .attach(Grid::row(4))
+                                    .child(host_button(id, ctx, "host as white", PieceColor::White))
+                                    .child(host_button(id, ctx, "host as black", PieceColor::Black))
                                     .build(ctx),
                             )
                             .child(
                                 Stack::new()
                                     .h_align("center")
                                     .spacing(5)
-                                    .attach(Grid::row(4))
+                                    .attach(Grid::row(6))
                                     .child(ipbox)
                                     .child(
                                         Button::new()
@@ -155,6 +173,7 @@ pub fn popup_start(id: Entity, ctx: &mut BuildContext, ip: String) -> (Entity, E
                                                 let cs: &mut ChessState = state.get_mut(id);
                                                 cs.action(Action::Connect);
                                                 cs.action(Action::ClosePopups);
+                                                cs.action(Action::StartGame);
 
                                                 true
                                             })
@@ -162,6 +181,32 @@ pub fn popup_start(id: Entity, ctx: &mut BuildContext, ip: String) -> (Entity, E
                                     )
                                     .build(ctx),
                             )
+                            .child(
+                                Stack::new()
+                                    .h_align("center")
+                                    .spacing(5)
+                                    .attach(Grid::row(8))
+                                    .child(enginebox)
+                                    .child(engine_button(
+                                        id,
+                                        ctx,
+                                        "engine plays black",
+                                        EngineRole::Opponent(PieceColor::Black),
+                                    ))
+                                    .child(engine_button(
+                                        id,
+                                        ctx,
+                                        "engine plays white",
+                                        EngineRole::Opponent(PieceColor::White),
+                                    ))
+                                    .child(engine_button(
+                                        id,
+                                        ctx,
+                                        "analyze with engine",
+                                        EngineRole::Analysis,
+                                    ))
+                                    .build(ctx),
+                            )
                             .build(ctx),
                     )
                     .build(ctx),
@@ -169,3 +214,33 @@ pub fn popup_start(id: Entity, ctx: &mut BuildContext, ip: String) -> (Entity, E
             .build(ctx),
     )
 }
+
+fn engine_button(id: Entity, ctx: &mut BuildContext, text: &str, role: EngineRole) -> Entity {
+    Button::new()
+        .text(text)
+        .on_click(move |state, _| {
+            let cs: &mut ChessState = state.get_mut(id);
+            // Actions run last in first out: the engine starts before the
+            // game, which then waits for it
+            cs.action(Action::StartGame);
+            cs.action(Action::ClosePopups);
+            cs.action(Action::StartEngine(role));
+
+            true
+        })
+        .build(ctx)
+}
+
+fn host_button(id: Entity, ctx: &mut BuildContext, text: &str, color: PieceColor) -> Entity {
+    Button::new()
+        .text(text)
+        .on_click(move |state, _| {
+            let cs: &mut ChessState = state.get_mut(id);
+            cs.action(Action::ClosePopups);
+            cs.host(color);
+            cs.action(Action::StartGame);
+
+            true
+        })
+        .build(ctx)
+}
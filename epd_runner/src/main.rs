@@ -0,0 +1,135 @@
+use chess_engine::epd::{self, EpdRecord};
+use chess_engine::uci::{Engine, Limit};
+
+use std::env;
+use std::fs;
+use std::process;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: epd_runner [options] -e <engine> <suite.epd>...
+
+options:
+    -e, --engine <path>      UCI engine to test
+    --depth <n>              search every position to depth n
+    --movetime <ms>          search every position for ms milliseconds (default 1000)
+    --option <name=value>    set a UCI option before starting, can be repeated";
+
+// How long past the time limit to wait for a bestmove, or for a whole depth
+// limited search
+const GRACE: Duration = Duration::from_secs(10);
+const DEPTH_TIMEOUT: Duration = Duration::from_secs(3600);
+
+fn main() {
+    let mut engine = None;
+    let mut limit = Limit::MoveTime(1000);
+    let mut options = Vec::new();
+    let mut inputs = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-e" | "--engine" => engine = Some(value(&arg, args.next())),
+            "--depth" => limit = Limit::Depth(number(&arg, args.next())),
+            "--movetime" => limit = Limit::MoveTime(number(&arg, args.next())),
+            "--option" => {
+                let option = value(&arg, args.next());
+                match option.split_once('=') {
+                    Some((name, value)) => options.push((name.to_owned(), value.to_owned())),
+                    None => fail(&format!("--option expects name=value, got {}", option)),
+                }
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with('-') => fail(&format!("unknown option {}", arg)),
+            _ => inputs.push(arg),
+        }
+    }
+
+    let path = engine.unwrap_or_else(|| fail("no engine given"));
+    if inputs.is_empty() {
+        fail("no EPD files given");
+    }
+
+    let mut records: Vec<EpdRecord> = Vec::new();
+    for input in &inputs {
+        let text = fs::read_to_string(input)
+            .unwrap_or_else(|e| fail(&format!("couldn't read {}: {}", input, e)));
+
+        for (i, line) in text.lines().enumerate() {
+            match epd::parse_line(line) {
+                Some(record) => records.push(record),
+                None if line.trim().is_empty() || line.trim().starts_with('#') => {}
+                None => eprintln!("{}:{}: skipping unreadable position", input, i + 1),
+            }
+        }
+    }
+
+    let mut engine =
+        Engine::spawn(&path).unwrap_or_else(|e| fail(&format!("couldn't start {}: {}", path, e)));
+    for (name, value) in &options {
+        engine
+            .set_option(name, Some(value))
+            .unwrap_or_else(|e| fail(&format!("couldn't set {}: {}", name, e)));
+    }
+
+    let timeout = match limit {
+        Limit::MoveTime(time) => Duration::from_millis(time) + GRACE,
+        _ => DEPTH_TIMEOUT,
+    };
+
+    let start = Instant::now();
+    let mut solved = 0;
+    for (i, record) in records.iter().enumerate() {
+        let id = record.id.clone().unwrap_or_else(|| format!("#{}", i + 1));
+        let result = record
+            .solve(&mut engine, limit, timeout)
+            .unwrap_or_else(|e| fail(&format!("{}: {}", id, e)));
+
+        let mut expected = Vec::new();
+        if !record.bm.is_empty() {
+            expected.push(format!("bm {}", record.bm.join(" ")));
+        }
+        if !record.am.is_empty() {
+            expected.push(format!("am {}", record.am.join(" ")));
+        }
+
+        if result.solved {
+            solved += 1;
+        }
+        println!(
+            "{}: {} {} ({}) {:.2}s",
+            id,
+            if result.solved { "solved" } else { "failed" },
+            result.san.as_deref().unwrap_or("-"),
+            expected.join(", "),
+            result.time.as_secs_f64()
+        );
+    }
+
+    println!(
+        "\nsolved {} of {}, failed {}, total time {:.1}s",
+        solved,
+        records.len(),
+        records.len() - solved,
+        start.elapsed().as_secs_f64()
+    );
+}
+
+fn value(option: &str, value: Option<String>) -> String {
+    value.unwrap_or_else(|| fail(&format!("{} needs a value", option)))
+}
+
+fn number<T: std::str::FromStr>(option: &str, value: Option<String>) -> T {
+    let value = self::value(option, value);
+
+    value
+        .parse()
+        .unwrap_or_else(|_| fail(&format!("{} expects a number, got {}", option, value)))
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(1);
+}
@@ -0,0 +1,348 @@
+use chess_engine::arena::{self, Adjudication, GameRecord, Opening, TimeControl};
+use chess_engine::epd;
+use chess_engine::fen;
+use chess_engine::game::Outcome;
+use chess_engine::pgn;
+use chess_engine::sprt::{MatchScore, Sprt, Verdict};
+use chess_engine::uci::{Engine, UciMove};
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::process;
+
+const USAGE: &str = "usage: match_runner [options] --first <engine> --second <engine>
+
+options:
+    --first <path>            engine being tested
+    --second <path>           engine it plays against
+    --first-option <n=v>      set a UCI option of the first engine, can be repeated
+    --second-option <n=v>     set a UCI option of the second engine, can be repeated
+    --openings <file>         .epd or .pgn file of openings, each is played twice
+                              with colours swapped (default the start position)
+    --plies <n>               only play the first n moves of PGN openings
+    --games <n>               number of games (default 100)
+    --tc <base+inc>           clock in seconds, like 10+0.1 (default)
+    --movetime <ms>           search every move for ms milliseconds
+    --depth <n>               search every move to depth n
+    --resign <cp,moves>       adjudicate a loss when both engines agree for
+                              that many moves (default 1000,3)
+    --draw <cp,moves,after>   adjudicate a draw when both engines agree after
+                              move after (default 10,8,40)
+    --max-moves <n>           draw games longer than n moves
+    --no-adjudication         play every game out
+    --sprt <elo0,elo1,a,b>    stop once the test decides (e.g. 0,5,0.05,0.05)
+    -o, --pgn <file>          where to save the games (default match.pgn)";
+
+fn main() {
+    let mut paths = [None, None];
+    let mut options = [Vec::new(), Vec::new()];
+    let mut openings_file = None;
+    let mut plies = None;
+    let mut games = 100;
+    let mut time_control = TimeControl::Clock {
+        base: 10000,
+        increment: 100,
+    };
+    let mut adjudication = Adjudication::default();
+    let mut sprt = None;
+    let mut output = "match.pgn".to_owned();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--first" => paths[0] = Some(value(&arg, args.next())),
+            "--second" => paths[1] = Some(value(&arg, args.next())),
+            "--first-option" | "--second-option" => {
+                let option = value(&arg, args.next());
+                let index = if arg == "--first-option" { 0 } else { 1 };
+                match option.split_once('=') {
+                    Some((name, value)) => options[index].push((name.to_owned(), value.to_owned())),
+                    None => fail(&format!("{} expects name=value, got {}", arg, option)),
+                }
+            }
+            "--openings" => openings_file = Some(value(&arg, args.next())),
+            "--plies" => plies = Some(number(&arg, args.next())),
+            "--games" => games = number(&arg, args.next()),
+            "--tc" => time_control = clock(&value(&arg, args.next())),
+            "--movetime" => time_control = TimeControl::MoveTime(number(&arg, args.next())),
+            "--depth" => time_control = TimeControl::Depth(number(&arg, args.next())),
+            "--resign" => {
+                let values: Vec<i32> = numbers(&arg, args.next(), 2);
+                adjudication.resign_score = Some(values[0]);
+                adjudication.resign_moves = values[1] as usize;
+            }
+            "--draw" => {
+                let values: Vec<i32> = numbers(&arg, args.next(), 3);
+                adjudication.draw_score = Some(values[0]);
+                adjudication.draw_moves = values[1] as usize;
+                adjudication.draw_after = values[2] as usize;
+            }
+            "--max-moves" => adjudication.max_moves = Some(number(&arg, args.next())),
+            "--no-adjudication" => {
+                adjudication.resign_score = None;
+                adjudication.draw_score = None;
+                adjudication.max_moves = None;
+            }
+            "--sprt" => {
+                let values: Vec<f64> = numbers(&arg, args.next(), 4);
+                sprt = Some(Sprt {
+                    elo0: values[0],
+                    elo1: values[1],
+                    alpha: values[2],
+                    beta: values[3],
+                });
+            }
+            "-o" | "--pgn" => output = value(&arg, args.next()),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => fail(&format!("unknown option {}", arg)),
+        }
+    }
+
+    let openings = match &openings_file {
+        Some(path) => load_openings(path, plies),
+        None => vec![Opening::start()],
+    };
+    if openings.is_empty() {
+        fail("no openings found");
+    }
+
+    let mut engines: Vec<Engine> = paths
+        .iter()
+        .zip(&options)
+        .map(|(path, options)| {
+            let path = path
+                .clone()
+                .unwrap_or_else(|| fail("both --first and --second are needed"));
+            let mut engine = Engine::spawn(&path)
+                .unwrap_or_else(|e| fail(&format!("couldn't start {}: {}", path, e)));
+
+            for (name, value) in options {
+                engine
+                    .set_option(name, Some(value))
+                    .unwrap_or_else(|e| fail(&format!("couldn't set {}: {}", name, e)));
+            }
+            engine
+        })
+        .collect();
+
+    // Both sides may be the same engine with different options
+    let mut names = [engines[0].name.clone(), engines[1].name.clone()];
+    if names[0] == names[1] {
+        names[0].push_str(" (1)");
+        names[1].push_str(" (2)");
+    }
+
+    let mut pgn_file = File::create(&output)
+        .unwrap_or_else(|e| fail(&format!("couldn't create {}: {}", output, e)));
+    let event = format!("{} vs {}", names[0], names[1]);
+
+    // From the first engine's side
+    let mut score = MatchScore::default();
+    let mut unfinished = 0;
+
+    for round in 0..games {
+        let opening = &openings[(round / 2) % openings.len()];
+        let first_white = round % 2 == 0;
+
+        let (white, black) = engines.split_at_mut(1);
+        let (white, black) = if first_white {
+            (&mut white[0], &mut black[0])
+        } else {
+            (&mut black[0], &mut white[0])
+        };
+
+        let mut record = arena::play_game(white, black, opening, time_control, &adjudication)
+            .unwrap_or_else(|e| fail(&format!("game {}: {}", round + 1, e)));
+        if first_white {
+            record.white = names[0].clone();
+            record.black = names[1].clone();
+        } else {
+            record.white = names[1].clone();
+            record.black = names[0].clone();
+        }
+
+        pgn_file
+            .write_all(format!("{}\n", record.to_pgn(&event, round + 1)).as_bytes())
+            .unwrap_or_else(|e| fail(&format!("couldn't write {}: {}", output, e)));
+
+        let outcome = record
+            .result
+            .map(|outcome| if first_white { outcome } else { outcome.flip() });
+        match outcome {
+            Some(Outcome::WhiteWins) => score.wins += 1,
+            Some(Outcome::BlackWins) => score.losses += 1,
+            Some(Outcome::Draw) => score.draws += 1,
+            None => unfinished += 1,
+        }
+
+        report(round + 1, &record, &score, sprt.as_ref());
+
+        if let Some(sprt) = &sprt {
+            match sprt.verdict(&score) {
+                Verdict::Accepted => {
+                    println!("\nSPRT: H1 accepted, {} is stronger", names[0]);
+                    break;
+                }
+                Verdict::Rejected => {
+                    println!("\nSPRT: H0 accepted, {} isn't stronger", names[0]);
+                    break;
+                }
+                Verdict::Undecided => {}
+            }
+        }
+    }
+
+    if unfinished > 0 {
+        println!("{} unfinished games weren't counted", unfinished);
+    }
+    println!("games saved to {}", output);
+}
+
+fn report(round: usize, record: &GameRecord, score: &MatchScore, sprt: Option<&Sprt>) {
+    println!(
+        "game {}: {} - {} {} ({})",
+        round,
+        record.white,
+        record.black,
+        record.result_text(),
+        record.termination
+    );
+
+    if score.games() == 0 {
+        return;
+    }
+
+    let elo = match score.elo() {
+        Some((diff, margin)) => format!("{:.1} +/- {:.1}", diff, margin),
+        None => "-".to_owned(),
+    };
+    println!(
+        "  W/D/L {}/{}/{}, score {:.1}%, elo {}",
+        score.wins,
+        score.draws,
+        score.losses,
+        100.0 * score.score(),
+        elo
+    );
+
+    if let Some(sprt) = sprt {
+        let (lower, upper) = sprt.bounds();
+        println!(
+            "  SPRT elo0 {} elo1 {}: llr {:.2} ({:.2}, {:.2})",
+            sprt.elo0,
+            sprt.elo1,
+            score.llr(sprt.elo0, sprt.elo1),
+            lower,
+            upper
+        );
+    }
+}
+
+// EPD positions or the moves of PGN games, optionally cut down to `plies`
+fn load_openings(path: &str, plies: Option<usize>) -> Vec<Opening> {
+    let text = fs::read_to_string(path)
+        .unwrap_or_else(|e| fail(&format!("couldn't read {}: {}", path, e)));
+
+    if path.ends_with(".epd") {
+        return text
+            .lines()
+            .filter_map(epd::parse_line)
+            .map(|record| Opening {
+                fen: record.fen,
+                moves: Vec::new(),
+            })
+            .collect();
+    }
+
+    let mut openings = Vec::new();
+    for (i, game) in pgn::parse(&text).iter().enumerate() {
+        let position = game.tag("FEN").unwrap_or(fen::START).to_owned();
+        let mut board = match fen::parse(&position) {
+            Some(board) => board,
+            None => {
+                eprintln!("{}: skipping game {}, bad FEN", path, i + 1);
+                continue;
+            }
+        };
+
+        let mut moves = Vec::new();
+        for san in game.moves.iter().take(plies.unwrap_or(usize::MAX)) {
+            match pgn::play_san(&mut board, san) {
+                Some(mv) => moves.push(UciMove {
+                    from: mv.from,
+                    to: mv.to,
+                    promotion: mv.promotion,
+                }),
+                None => {
+                    eprintln!("{}: game {} stops at unreadable move {}", path, i + 1, san);
+                    break;
+                }
+            }
+        }
+
+        openings.push(Opening {
+            fen: position,
+            moves,
+        });
+    }
+
+    openings
+}
+
+// Parses "base+increment" in seconds
+fn clock(text: &str) -> TimeControl {
+    let (base, increment) = text.split_once('+').unwrap_or((text, "0"));
+    let seconds = |s: &str| -> u64 {
+        let seconds: f64 = s
+            .parse()
+            .unwrap_or_else(|_| fail(&format!("--tc expects base+increment, got {}", text)));
+        (seconds * 1000.0) as u64
+    };
+
+    TimeControl::Clock {
+        base: seconds(base),
+        increment: seconds(increment),
+    }
+}
+
+fn value(option: &str, value: Option<String>) -> String {
+    value.unwrap_or_else(|| fail(&format!("{} needs a value", option)))
+}
+
+fn number<T: std::str::FromStr>(option: &str, value: Option<String>) -> T {
+    let value = self::value(option, value);
+
+    value
+        .parse()
+        .unwrap_or_else(|_| fail(&format!("{} expects a number, got {}", option, value)))
+}
+
+// Comma separated numbers, exactly `count` of them
+fn numbers<T: std::str::FromStr>(option: &str, value: Option<String>, count: usize) -> Vec<T> {
+    let value = self::value(option, value);
+    let numbers: Vec<T> = value
+        .split(',')
+        .map(|n| {
+            n.trim()
+                .parse()
+                .unwrap_or_else(|_| fail(&format!("{} expects numbers, got {}", option, value)))
+        })
+        .collect();
+
+    if numbers.len() != count {
+        fail(&format!(
+            "{} expects {} numbers, got {}",
+            option, count, value
+        ));
+    }
+    numbers
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(1);
+}